@@ -1,24 +1,48 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::str::FromStr;
+use std::sync::{
+    Mutex,
+    atomic::{AtomicU32, Ordering},
+};
+use std::time::Duration;
 
-use anyhow::{Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail, ensure};
 
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
 use derive_more::{Display, From, Into};
 use graphql_client::{GraphQLQuery, Response};
-use reqwest::{Client, header, header::HeaderValue};
+use reqwest::{
+    Client, StatusCode,
+    header::{self, HeaderMap, HeaderValue},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
 use crate::{
     anime::{AnimeId, EpisodeId},
+    cache::{self, Kind},
     config,
+    error::RequestError,
+    journal::{Entry, Journal},
+    tracker::Tracker,
     ui::Tui,
 };
 
 const ENDPOINT: &str = "https://graphql.anilist.co";
+/// Most anime walked through the relations of a franchise
+const MAX_FRANCHISE: usize = 100;
 
 /// Identifies an AniList API client (OAuth application ID).
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, From, Display)]
 #[display("{_0}")]
 pub struct AnilistId(u32);
 
+impl Default for AnilistId {
+    fn default() -> Self {
+        Self(4047)
+    }
+}
+
 impl FromStr for AnilistId {
     type Err = <u32 as FromStr>::Err;
 
@@ -27,43 +51,509 @@ impl FromStr for AnilistId {
     }
 }
 
+/// Language of the titles shown, from the `[anilist]` config table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TitleLanguage {
+    #[default]
+    Romaji,
+    English,
+    Native,
+}
+
+impl FromStr for TitleLanguage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "romaji" => Ok(Self::Romaji),
+            "english" => Ok(Self::English),
+            "native" => Ok(Self::Native),
+            _ => bail!("expected one of `romaji`, `english`, `native`"),
+        }
+    }
+}
+
+impl TitleLanguage {
+    /// Loads the language from the configuration, romaji when unset.
+    pub fn load() -> Result<Self> {
+        match config::load("anilist", "title") {
+            Ok(value) => value
+                .parse()
+                .context("Invalid configuration value `anilist.title`"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+/// Titles of an anime in the languages known by AniList.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Titles {
+    romaji: Option<String>,
+    english: Option<String>,
+    native: Option<String>,
+}
+
+impl Titles {
+    /// All the titles without duplicates, the one in the language first and
+    /// then the romaji one.
+    pub fn ordered(self, language: TitleLanguage) -> Vec<String> {
+        let Self {
+            romaji,
+            english,
+            native,
+        } = self;
+        let titles = match language {
+            TitleLanguage::Romaji => [romaji, english, native],
+            TitleLanguage::English => [english, romaji, native],
+            TitleLanguage::Native => [native, romaji, english],
+        };
+
+        let mut ordered = Vec::new();
+        for title in titles.into_iter().flatten() {
+            if !title.is_empty() && !ordered.contains(&title) {
+                ordered.push(title);
+            }
+        }
+
+        ordered
+    }
+
+    /// Title in the language, or the romaji one when missing.
+    pub fn get(self, language: TitleLanguage) -> Option<String> {
+        self.ordered(language).into_iter().next()
+    }
+}
+
+macro_rules! impl_titles {
+    ($($module:ident),+) => {
+        $(
+            impl From<$module::MediaTitles> for Titles {
+                fn from(titles: $module::MediaTitles) -> Self {
+                    Self {
+                        romaji: titles.romaji,
+                        english: titles.english,
+                        native: titles.native,
+                    }
+                }
+            }
+        )+
+    };
+}
+
 #[derive(Debug)]
 pub struct WatchingAnime {
     watched: i64,
     id: i64,
     title: String,
+    /// The other titles and the synonyms, to search the archives
+    alternatives: Vec<String>,
+    /// Lists the anime is in, by status (eg. `Watching`) and custom ones
+    lists: Vec<String>,
 }
 
 impl WatchingAnime {
+    pub fn new(id: AnimeId, title: String, watched: i64) -> Self {
+        Self {
+            id: id.into(),
+            title,
+            watched,
+            alternatives: Vec::new(),
+            lists: Vec::new(),
+        }
+    }
+
     pub fn title(&self) -> &str {
         &self.title
     }
 
+    /// The shown title, followed by the alternative ones.
+    pub fn titles(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.title.as_str()).chain(self.alternatives.iter().map(String::as_str))
+    }
+
     pub fn watched(&self) -> u32 {
         self.watched.max(0) as u32
     }
 
+    pub fn lists(&self) -> &[String] {
+        &self.lists
+    }
+
+    /// Checks if the anime is in the list, ignoring the case of the name.
+    pub fn is_in(&self, list: &str) -> bool {
+        self.lists.iter().any(|l| l.eq_ignore_ascii_case(list))
+    }
+
     pub fn id(&self) -> AnimeId {
         self.id.into()
     }
 }
 
+#[derive(Default)]
 pub struct Progress {
+    /// Zero when unknown, while the anime is airing
     episodes: i64,
     progress: i64,
+    title: String,
+    status: Option<ListStatus>,
+    /// Times the series has been rewatched
+    repeat: i64,
 }
 
 impl Progress {
+    pub fn new(episodes: i64, progress: i64, title: String) -> Self {
+        Self {
+            episodes,
+            progress,
+            title,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_status(mut self, status: Option<ListStatus>, repeat: i64) -> Self {
+        self.status = status;
+        self.repeat = repeat;
+        self
+    }
+
     pub fn latest(&self) -> EpisodeId {
         self.progress.into()
     }
 
-    fn has_seen(&self, n: EpisodeId) -> bool {
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn has_seen(&self, n: EpisodeId) -> bool {
         self.progress >= n.into()
     }
 
-    fn is_last(&self, n: EpisodeId) -> bool {
-        self.episodes <= n.into()
+    /// Checks if the episode ends the series, never while the number of
+    /// episodes is unknown.
+    pub fn is_last(&self, n: EpisodeId) -> bool {
+        self.episodes > 0 && self.episodes <= n.into()
+    }
+
+    pub fn is_repeating(&self) -> bool {
+        self.status == Some(ListStatus::Repeating)
+    }
+
+    /// Checks if watching the episode would be a new rewatch: the series is
+    /// completed, or the current rewatch is already past it.
+    pub fn starts_rewatch(&self, n: EpisodeId) -> bool {
+        match self.status {
+            Some(ListStatus::Completed) => true,
+            Some(ListStatus::Repeating) => self.has_seen(n),
+            _ => false,
+        }
+    }
+
+    /// Status of the entry once the episode is watched, `None` when already seen.
+    pub fn status_after(&self, n: EpisodeId) -> Option<ListStatus> {
+        let status = if self.has_seen(n) {
+            return None;
+        } else if self.is_last(n) {
+            ListStatus::Completed
+        } else if self.is_repeating() {
+            ListStatus::Repeating
+        } else {
+            ListStatus::Current
+        };
+
+        Some(status)
+    }
+
+    /// Rewatches count once the episode is watched, `None` when unchanged.
+    pub fn repeat_after(&self, n: EpisodeId) -> Option<i64> {
+        (self.is_repeating() && self.is_last(n)).then_some(self.repeat + 1)
+    }
+}
+
+/// An anime of the user list, with any status.
+#[derive(Debug)]
+pub struct ListEntry {
+    id: i64,
+    mal_id: Option<i64>,
+    title: String,
+    status: ListStatus,
+    progress: i64,
+    episodes: Option<i64>,
+    score: f64,
+    /// Score out of 10, whatever the format of the user
+    rating: f64,
+    repeat: i64,
+    notes: Option<String>,
+    started_at: Option<NaiveDate>,
+    completed_at: Option<NaiveDate>,
+}
+
+impl ListEntry {
+    pub fn id(&self) -> AnimeId {
+        self.id.into()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn status(&self) -> ListStatus {
+        self.status
+    }
+
+    /// Watched episodes over the total ones, eg. `3/12` or `3/?` while airing.
+    pub fn progress(&self) -> String {
+        match self.episodes {
+            Some(episodes) => format!("{}/{episodes}", self.progress),
+            None => format!("{}/?", self.progress),
+        }
+    }
+
+    /// Score in the format chosen by the user, none when unrated.
+    pub fn score(&self) -> Option<f64> {
+        (self.score > 0.0).then_some(self.score)
+    }
+
+    /// Score out of 10, none when unrated.
+    pub fn rating(&self) -> Option<f64> {
+        (self.rating > 0.0).then_some(self.rating)
+    }
+
+    pub fn mal_id(&self) -> Option<u32> {
+        self.mal_id.and_then(|id| id.try_into().ok())
+    }
+
+    pub fn latest(&self) -> EpisodeId {
+        self.progress.into()
+    }
+
+    pub fn episodes(&self) -> Option<u32> {
+        self.episodes.and_then(|e| e.try_into().ok())
+    }
+
+    /// Times the series has been rewatched
+    pub fn repeat(&self) -> u32 {
+        self.repeat.max(0) as u32
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref().filter(|n| !n.is_empty())
+    }
+
+    pub fn started_at(&self) -> Option<NaiveDate> {
+        self.started_at
+    }
+
+    pub fn completed_at(&self) -> Option<NaiveDate> {
+        self.completed_at
+    }
+}
+
+/// An anime found by searching AniList.
+#[derive(Debug)]
+pub struct MediaInfo {
+    id: i64,
+    title: String,
+    /// The other titles, followed by the synonyms
+    synonyms: Vec<String>,
+    format: Option<String>,
+    year: Option<i64>,
+    episodes: Option<i64>,
+    /// Known only for the related anime
+    started_at: Option<NaiveDate>,
+}
+
+impl MediaInfo {
+    pub fn id(&self) -> AnimeId {
+        self.id.into()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn synonyms(&self) -> &[String] {
+        &self.synonyms
+    }
+
+    /// The shown title, followed by the other titles and the synonyms.
+    pub fn titles(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.title.as_str()).chain(self.synonyms.iter().map(String::as_str))
+    }
+
+    pub fn format(&self) -> Option<&str> {
+        self.format.as_deref()
+    }
+
+    pub fn year(&self) -> Option<i64> {
+        self.year
+    }
+
+    pub fn episodes(&self) -> Option<i64> {
+        self.episodes
+    }
+
+    /// Checks if any title of the anime is exactly the query, ignoring the case.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim();
+
+        self.titles().any(|t| t.eq_ignore_ascii_case(query))
+    }
+}
+
+/// Formats of the anime that can be picked from a franchise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display, clap::ValueEnum)]
+pub enum Format {
+    #[display("TV")]
+    Tv,
+    Movie,
+    #[display("OVA")]
+    Ova,
+    Special,
+}
+
+impl Format {
+    pub fn matches(&self, media: &MediaInfo) -> bool {
+        media.format() == Some(self.to_string().as_str())
+    }
+}
+
+/// How an anime is related to another one, in the order they are suggested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Display)]
+pub enum Relation {
+    Sequel,
+    Prequel,
+    #[display("Side story")]
+    SideStory,
+}
+
+/// An anime related to another one.
+#[derive(Debug)]
+pub struct RelatedMedia {
+    relation: Relation,
+    media: MediaInfo,
+}
+
+impl RelatedMedia {
+    pub fn relation(&self) -> Relation {
+        self.relation
+    }
+
+    pub fn media(&self) -> &MediaInfo {
+        &self.media
+    }
+}
+
+impl std::fmt::Display for MediaInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title)?;
+
+        let details: Vec<_> = self
+            .format
+            .clone()
+            .into_iter()
+            .chain(self.year.map(|y| y.to_string()))
+            .collect();
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// An upcoming episode of a watched anime.
+#[derive(Debug, Clone)]
+pub struct Airing {
+    id: i64,
+    title: String,
+    episode: i64,
+    /// Unix timestamp of the release
+    airing_at: i64,
+    /// Seconds left to the release
+    time_until: i64,
+    /// Length of the episode in minutes
+    duration: Option<i64>,
+}
+
+impl Airing {
+    pub fn id(&self) -> AnimeId {
+        self.id.into()
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn episode(&self) -> EpisodeId {
+        self.episode.into()
+    }
+
+    pub fn airing_at(&self) -> i64 {
+        self.airing_at
+    }
+
+    pub fn time_until(&self) -> i64 {
+        self.time_until
+    }
+
+    pub fn duration(&self) -> Option<i64> {
+        self.duration
+    }
+}
+
+#[cfg(test)]
+impl Airing {
+    pub fn new(id: i64, title: &str, episode: i64, airing_at: i64, time_until: i64) -> Self {
+        Self {
+            id,
+            title: title.to_string(),
+            episode,
+            airing_at,
+            time_until,
+            duration: None,
+        }
+    }
+}
+
+/// An episode aired since the last check, from the AniList notifications.
+#[derive(Debug)]
+pub struct AiredEpisode {
+    episode: i64,
+    /// Unix timestamp of the notification
+    created_at: i64,
+    media: MediaInfo,
+}
+
+impl AiredEpisode {
+    pub fn episode(&self) -> EpisodeId {
+        self.episode.into()
+    }
+
+    pub fn created_at(&self) -> i64 {
+        self.created_at
+    }
+
+    pub fn media(&self) -> &MediaInfo {
+        &self.media
+    }
+}
+
+#[cfg(test)]
+impl AiredEpisode {
+    pub fn new(anime: AnimeId, title: &str, episode: i64, created_at: i64) -> Self {
+        Self {
+            episode,
+            created_at,
+            media: MediaInfo {
+                id: anime.into(),
+                title: title.to_string(),
+                synonyms: vec![],
+                format: None,
+                year: None,
+                episodes: None,
+                started_at: None,
+            },
+        }
     }
 }
 
@@ -75,184 +565,1481 @@ impl Progress {
 struct ProgressQuery;
 
 impl ProgressQuery {
-    async fn get(client: &Client, id: AnimeId) -> Option<Progress> {
+    async fn get(anilist: &Anilist, id: AnimeId) -> Option<Progress> {
         let id = Some(id.into());
         let query = Self::build_query(progress_query::Variables { id });
-        let response = client.post(ENDPOINT).json(&query).send().await.ok()?;
+        let json: Response<progress_query::ResponseData> =
+            anilist.fetch(Kind::Progress, &query).await.ok()?;
+
+        let media = json.data?.media?;
+
+        // unknown while the anime is airing
+        let episodes = media.episodes.unwrap_or_default();
+        let title = media
+            .title
+            .and_then(|t| Titles::from(t).get(anilist.language))
+            .unwrap_or_default();
+        let entry = media.media_list_entry?;
+        let progress = entry.progress?;
+        let status = entry.status.and_then(|s| s.try_into().ok());
+
+        Some(
+            Progress::new(episodes, progress, title)
+                .with_status(status, entry.repeat.unwrap_or_default()),
+        )
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/progress_batch_query.graphql"
+)]
+struct ProgressBatchQuery;
+
+impl ProgressBatchQuery {
+    /// Progress of many anime, one page of results at a time.
+    async fn get(anilist: &Anilist, ids: &[AnimeId]) -> Result<HashMap<AnimeId, Progress>> {
+        let ids: Vec<_> = ids.iter().map(|&id| Some(id.into())).collect();
+
+        let mut progress = HashMap::new();
+        for page in 1.. {
+            let variables = progress_batch_query::Variables {
+                ids: Some(ids.clone()),
+                page: Some(page),
+            };
+            let query = Self::build_query(variables);
+            let json: Response<progress_batch_query::ResponseData> =
+                anilist.fetch(Kind::Progress, &query).await?;
+
+            let page = json.data.and_then(|d| d.page).context("no page")?;
+            for media in page.media.into_iter().flatten().flatten() {
+                // unknown while the anime is airing
+                let episodes = media.episodes.unwrap_or_default();
+                let Some(entry) = media.media_list_entry else {
+                    continue;
+                };
+                let Some(p) = entry.progress else {
+                    continue;
+                };
+                let title = media
+                    .title
+                    .and_then(|t| Titles::from(t).get(anilist.language))
+                    .unwrap_or_default();
+                let status = entry.status.and_then(|s| s.try_into().ok());
+                let repeat = entry.repeat.unwrap_or_default();
+
+                let entry = Progress::new(episodes, p, title).with_status(status, repeat);
+                progress.insert(media.id.into(), entry);
+            }
+
+            if !page
+                .page_info
+                .and_then(|i| i.has_next_page)
+                .unwrap_or(false)
+            {
+                break;
+            }
+        }
+
+        Ok(progress)
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/mapping_query.graphql"
+)]
+struct MappingQuery;
+
+impl MappingQuery {
+    /// Pairs of AniList and MyAnimeList ids, looked up by either of them.
+    async fn get(
+        anilist: &Anilist,
+        ids: Option<Vec<Option<i64>>>,
+        mal_ids: Option<Vec<Option<i64>>>,
+    ) -> Option<Vec<(AnimeId, u32)>> {
+        let mut pairs = Vec::new();
+        for page in 1.. {
+            let variables = mapping_query::Variables {
+                ids: ids.clone(),
+                mal_ids: mal_ids.clone(),
+                page: Some(page),
+            };
+            let query = Self::build_query(variables);
+            let json: Response<mapping_query::ResponseData> =
+                anilist.fetch(Kind::Mapping, &query).await.ok()?;
+
+            let page = json.data?.page?;
+            let media = page.media.into_iter().flatten().flatten();
+            pairs.extend(media.filter_map(|m| Some((m.id.into(), m.id_mal? as u32))));
+
+            if !page
+                .page_info
+                .and_then(|i| i.has_next_page)
+                .unwrap_or(false)
+            {
+                break;
+            }
+        }
+
+        Some(pairs)
+    }
+}
+
+/// Status of an anime in the user list.
+#[derive(
+    Clone,
+    Copy,
+    Debug,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Display,
+    Serialize,
+    Deserialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ListStatus {
+    Planning,
+    #[display("Watching")]
+    #[value(alias = "watching")]
+    Current,
+    #[display("Rewatching")]
+    #[value(alias = "rewatching")]
+    Repeating,
+    Paused,
+    Dropped,
+    Completed,
+}
+
+macro_rules! impl_list_status {
+    ($($module:ident),+) => {
+        $(
+            impl From<ListStatus> for $module::MediaListStatus {
+                fn from(status: ListStatus) -> Self {
+                    match status {
+                        ListStatus::Planning => Self::PLANNING,
+                        ListStatus::Current => Self::CURRENT,
+                        ListStatus::Repeating => Self::REPEATING,
+                        ListStatus::Paused => Self::PAUSED,
+                        ListStatus::Dropped => Self::DROPPED,
+                        ListStatus::Completed => Self::COMPLETED,
+                    }
+                }
+            }
+
+            impl TryFrom<$module::MediaListStatus> for ListStatus {
+                type Error = anyhow::Error;
+
+                fn try_from(status: $module::MediaListStatus) -> Result<Self> {
+                    use $module::MediaListStatus;
+
+                    match status {
+                        MediaListStatus::PLANNING => Ok(Self::Planning),
+                        MediaListStatus::CURRENT => Ok(Self::Current),
+                        MediaListStatus::REPEATING => Ok(Self::Repeating),
+                        MediaListStatus::PAUSED => Ok(Self::Paused),
+                        MediaListStatus::DROPPED => Ok(Self::Dropped),
+                        MediaListStatus::COMPLETED => Ok(Self::Completed),
+                        MediaListStatus::Other(s) => Err(anyhow!("unknown list status {s}")),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_titles!(
+    watching_query,
+    list_query,
+    schedule_query,
+    notifications_query,
+    progress_query,
+    progress_batch_query,
+    search_query,
+    relations_query
+);
+
+impl_list_status!(
+    progress_mutation,
+    list_query,
+    watching_query,
+    progress_query,
+    progress_batch_query
+);
+
+/// Changes to an entry of the user list, unset fields are left untouched.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EntryUpdate {
+    pub status: Option<ListStatus>,
+    pub progress: Option<EpisodeId>,
+    pub score: Option<f64>,
+    /// Score out of 100, whatever the format of the user
+    pub score_raw: Option<i64>,
+    pub notes: Option<String>,
+    pub started_at: Option<NaiveDate>,
+    pub completed_at: Option<NaiveDate>,
+    pub repeat: Option<i64>,
+}
+
+impl EntryUpdate {
+    /// Fills the review fields, keeping the ones already set.
+    pub fn with_review(self, review: Review) -> Self {
+        Self {
+            score: self.score.or(review.score),
+            notes: self.notes.or(review.notes),
+            completed_at: self.completed_at.or(review.completed_at),
+            ..self
+        }
+    }
+}
+
+/// What the user thinks of a completed series.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Review {
+    /// In the score format of the user
+    pub score: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<NaiveDate>,
+}
+
+/// The scale of the scores, chosen in the AniList settings.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Display)]
+pub enum ScoreFormat {
+    #[display("0-100")]
+    Point100,
+    #[display("0-10, one decimal")]
+    Point10Decimal,
+    #[default]
+    #[display("0-10")]
+    Point10,
+    #[display("1-5 stars")]
+    Point5,
+    #[display(":( :| :)")]
+    Point3,
+}
+
+impl ScoreFormat {
+    fn max(&self) -> f64 {
+        match self {
+            Self::Point100 => 100.0,
+            Self::Point10Decimal | Self::Point10 => 10.0,
+            Self::Point5 => 5.0,
+            Self::Point3 => 3.0,
+        }
+    }
+
+    /// Parses a score written in this format, `None` when it is out of scale.
+    pub fn parse(&self, input: &str) -> Option<f64> {
+        let input = input.trim();
+        let score = match self {
+            Self::Point10Decimal => (input.parse::<f64>().ok()? * 10.0).round() / 10.0,
+            Self::Point5 if !input.is_empty() && input.chars().all(|c| c == '★' || c == '*') => {
+                input.chars().count() as f64
+            }
+            Self::Point3 => match input {
+                ":(" => 1.0,
+                ":|" => 2.0,
+                ":)" => 3.0,
+                _ => input.parse::<u8>().ok()?.into(),
+            },
+            _ => input.parse::<u8>().ok()?.into(),
+        };
+
+        (0.0..=self.max()).contains(&score).then_some(score)
+    }
+}
+
+impl From<user_query::ScoreFormat> for ScoreFormat {
+    fn from(format: user_query::ScoreFormat) -> Self {
+        use user_query::ScoreFormat;
+
+        match format {
+            ScoreFormat::POINT_100 => Self::Point100,
+            ScoreFormat::POINT_10_DECIMAL => Self::Point10Decimal,
+            ScoreFormat::POINT_5 => Self::Point5,
+            ScoreFormat::POINT_3 => Self::Point3,
+            ScoreFormat::POINT_10 | ScoreFormat::Other(_) => Self::Point10,
+        }
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/progress_mutation.graphql",
+    skip_serializing_none
+)]
+struct ProgressMutation;
+
+impl ProgressMutation {
+    async fn put(anilist: &Anilist, id: AnimeId, update: EntryUpdate) -> Result<()> {
+        let EntryUpdate {
+            status,
+            progress,
+            score,
+            score_raw,
+            notes,
+            started_at,
+            completed_at,
+            repeat,
+        } = update;
+
+        let fuzzy_date = |date: NaiveDate| progress_mutation::FuzzyDateInput {
+            year: Some(date.year().into()),
+            month: Some(date.month().into()),
+            day: Some(date.day().into()),
+        };
+        let variables = progress_mutation::Variables {
+            status: status.map(|s| s.into()),
+            id: Some(id.into()),
+            progress: progress.map(|p| p.into()),
+            score,
+            score_raw,
+            notes,
+            started_at: started_at.map(fuzzy_date),
+            completed_at: completed_at.map(fuzzy_date),
+            repeat,
+        };
+
+        let query = ProgressMutation::build_query(variables);
+        anilist.post(&query).await?.error_for_status()?;
+        // the list changed, the progress has to be requested again
+        cache::invalidate(&[Kind::Progress, Kind::Watching]);
+
+        Ok(())
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/watching_query.graphql",
+    skip_serializing_none
+)]
+struct WatchingQuery;
+
+impl WatchingQuery {
+    /// Anime being watched or rewatched, or all the ones in the list with the
+    /// given name.
+    async fn get(
+        anilist: &Anilist,
+        id: UserId,
+        list_name: Option<&str>,
+    ) -> Option<Vec<WatchingAnime>> {
+        let status = match list_name {
+            Some(_) => None,
+            None => Some(vec![
+                Some(ListStatus::Current.into()),
+                Some(ListStatus::Repeating.into()),
+            ]),
+        };
+        let variables = watching_query::Variables {
+            id: Some(id.into()),
+            status,
+        };
+        let query = WatchingQuery::build_query(variables);
+        let json: Response<watching_query::ResponseData> =
+            anilist.fetch(Kind::Watching, &query).await.ok()?;
+
+        // one list for each status, the custom ones repeat their entries
+        let lists = json.data?.media_list_collection?.lists?;
+
+        let mut list: Vec<WatchingAnime> = Vec::new();
+        for l in lists.into_iter().flatten() {
+            let entries = l.entries.into_iter().flatten().flatten();
+            for anime in entries.filter_map(|e| Self::watching_anime(anilist, e)) {
+                match list.iter_mut().find(|a| a.id == anime.id) {
+                    Some(a) => a.lists.extend(l.name.clone()),
+                    None => list.push(WatchingAnime {
+                        lists: l.name.clone().into_iter().collect(),
+                        ..anime
+                    }),
+                }
+            }
+        }
+
+        if let Some(name) = list_name {
+            list.retain(|a| a.is_in(name));
+        }
+        list.sort_by(|a, b| (a.lists.first(), &a.title).cmp(&(b.lists.first(), &b.title)));
+
+        Some(list)
+    }
+
+    fn watching_anime(
+        anilist: &Anilist,
+        entry: watching_query::WatchingQueryMediaListCollectionListsEntries,
+    ) -> Option<WatchingAnime> {
+        let progress = entry.progress?;
+        let media = entry.media?;
+
+        let watched = match media.next_airing_episode {
+            Some(airing) => airing.episode - (progress + 1),
+            None => media.episodes? - progress,
+        };
+
+        let mut titles = Titles::from(media.title?).ordered(anilist.language);
+        if titles.is_empty() {
+            return None;
+        }
+        let title = titles.remove(0);
+        let alternatives = titles
+            .into_iter()
+            .chain(media.synonyms.into_iter().flatten().flatten())
+            .collect();
+
+        Some(WatchingAnime {
+            id: media.id,
+            title,
+            watched,
+            alternatives,
+            lists: Vec::new(),
+        })
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/list_query.graphql"
+)]
+struct ListQuery;
+
+impl ListQuery {
+    async fn get(
+        anilist: &Anilist,
+        id: UserId,
+        status: Option<ListStatus>,
+    ) -> Option<Vec<ListEntry>> {
+        let variables = list_query::Variables {
+            id: Some(id.into()),
+            status: status.map(|s| s.into()),
+        };
+        let query = ListQuery::build_query(variables);
+        let response = anilist.post(&query).await.ok()?;
         let json = response
-            .json::<Response<progress_query::ResponseData>>()
+            .json::<Response<list_query::ResponseData>>()
             .await
             .ok()?;
 
-        let media = json.data?.media?;
+        // custom lists repeat the entries of the status lists
+        let mut list: Vec<_> = json
+            .data?
+            .media_list_collection?
+            .lists?
+            .into_iter()
+            .flatten()
+            .filter(|list| list.is_custom_list != Some(true))
+            .flat_map(|list| list.entries.unwrap_or_default())
+            .flatten()
+            .filter_map(|entry| {
+                let media = entry.media?;
+                let date = |d: list_query::Date| {
+                    let (year, month, day) = (d.year?, d.month?, d.day?);
+                    NaiveDate::from_ymd_opt(
+                        year.try_into().ok()?,
+                        month.try_into().ok()?,
+                        day.try_into().ok()?,
+                    )
+                };
+
+                Some(ListEntry {
+                    id: media.id,
+                    mal_id: media.id_mal,
+                    title: Titles::from(media.title?).get(anilist.language)?,
+                    status: entry.status?.try_into().ok()?,
+                    progress: entry.progress.unwrap_or_default(),
+                    episodes: media.episodes,
+                    score: entry.score.unwrap_or_default(),
+                    rating: entry.rating.unwrap_or_default(),
+                    repeat: entry.repeat.unwrap_or_default(),
+                    notes: entry.notes,
+                    started_at: entry.started_at.and_then(date),
+                    completed_at: entry.completed_at.and_then(date),
+                })
+            })
+            .collect();
+
+        list.sort_by(|a, b| (a.status, &a.title).cmp(&(b.status, &b.title)));
+
+        Some(list)
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/entry_query.graphql"
+)]
+struct EntryQuery;
+
+impl EntryQuery {
+    /// Id of the list entry of an anime, which is not the id of the anime.
+    async fn get(anilist: &Anilist, id: AnimeId) -> Option<i64> {
+        let id = Some(id.into());
+        let query = EntryQuery::build_query(entry_query::Variables { id });
+        let response = anilist.post(&query).await.ok()?;
+        let json = response
+            .json::<Response<entry_query::ResponseData>>()
+            .await
+            .ok()?;
+
+        Some(json.data?.media?.media_list_entry?.id)
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/delete_mutation.graphql"
+)]
+struct DeleteMutation;
+
+impl DeleteMutation {
+    async fn delete(anilist: &Anilist, entry_id: i64) -> Result<()> {
+        let id = Some(entry_id);
+        let query = DeleteMutation::build_query(delete_mutation::Variables { id });
+        anilist.post(&query).await?.error_for_status()?;
+        // the list changed, the progress has to be requested again
+        cache::invalidate(&[Kind::Progress, Kind::Watching]);
+
+        Ok(())
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/search_query.graphql"
+)]
+struct SearchQuery;
+
+impl SearchQuery {
+    async fn get(anilist: &Anilist, search: &str) -> Option<Vec<MediaInfo>> {
+        let search = Some(search.to_string());
+        let query = SearchQuery::build_query(search_query::Variables { search });
+        let json: Response<search_query::ResponseData> =
+            anilist.fetch(Kind::Media, &query).await.ok()?;
+
+        let results = json
+            .data?
+            .page?
+            .media?
+            .into_iter()
+            .flatten()
+            .filter_map(|media| {
+                let mut titles = Titles::from(media.title?).ordered(anilist.language);
+                if titles.is_empty() {
+                    return None;
+                }
+                let title = titles.remove(0);
+                let synonyms = titles
+                    .into_iter()
+                    .chain(media.synonyms.into_iter().flatten().flatten())
+                    .collect();
+
+                Some(MediaInfo {
+                    id: media.id,
+                    title,
+                    synonyms,
+                    format: media.format.and_then(|f| f.name()),
+                    year: media.season_year,
+                    episodes: media.episodes,
+                    started_at: None,
+                })
+            })
+            .collect();
+
+        Some(results)
+    }
+}
+
+macro_rules! impl_format_name {
+    ($($module:ident),+) => {
+        $(
+            impl $module::MediaFormat {
+                /// Name of an anime format, none for the other media.
+                fn name(self) -> Option<String> {
+                    let name = match self {
+                        Self::TV => "TV",
+                        Self::TV_SHORT => "TV Short",
+                        Self::MOVIE => "Movie",
+                        Self::SPECIAL => "Special",
+                        Self::OVA => "OVA",
+                        Self::ONA => "ONA",
+                        Self::MUSIC => "Music",
+                        _ => return None,
+                    };
+
+                    Some(name.to_string())
+                }
+            }
+        )+
+    };
+}
+
+impl_format_name!(search_query, relations_query);
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/relations_query.graphql"
+)]
+struct RelationsQuery;
+
+impl RelationsQuery {
+    /// The anime with the given id and the ones related to it.
+    async fn get(anilist: &Anilist, id: AnimeId) -> Option<(MediaInfo, Vec<RelatedMedia>)> {
+        let id = Some(id.into());
+        let query = RelationsQuery::build_query(relations_query::Variables { id });
+        let json: Response<relations_query::ResponseData> =
+            anilist.fetch(Kind::Media, &query).await.ok()?;
+
+        let root = json.data?.media?;
+        let media = Self::media_info(anilist, root.media_node)?;
+
+        let mut related: Vec<_> = root
+            .relations?
+            .edges?
+            .into_iter()
+            .flatten()
+            .filter_map(|edge| {
+                let relation = match edge.relation_type? {
+                    relations_query::MediaRelation::SEQUEL => Relation::Sequel,
+                    relations_query::MediaRelation::PREQUEL => Relation::Prequel,
+                    relations_query::MediaRelation::SIDE_STORY => Relation::SideStory,
+                    _ => return None,
+                };
+
+                let node = edge.node?;
+                if !matches!(node.type_, Some(relations_query::MediaType::ANIME)) {
+                    return None;
+                }
+                let media = Self::media_info(anilist, node.media_node)?;
+
+                Some(RelatedMedia { relation, media })
+            })
+            .collect();
+        related.sort_by_key(|r| (r.relation, r.media.year.unwrap_or(i64::MAX)));
+
+        Some((media, related))
+    }
+
+    fn media_info(anilist: &Anilist, media: relations_query::MediaNode) -> Option<MediaInfo> {
+        let mut titles = Titles::from(media.title?).ordered(anilist.language);
+        if titles.is_empty() {
+            return None;
+        }
+        let title = titles.remove(0);
+        let synonyms = titles
+            .into_iter()
+            .chain(media.synonyms.into_iter().flatten().flatten())
+            .collect();
+        let started_at = media.start_date.and_then(|d| {
+            let month = d.month.unwrap_or(1).try_into().ok()?;
+            let day = d.day.unwrap_or(1).try_into().ok()?;
+            NaiveDate::from_ymd_opt(d.year?.try_into().ok()?, month, day)
+        });
+
+        Some(MediaInfo {
+            id: media.id,
+            title,
+            synonyms,
+            format: media.format.and_then(|f| f.name()),
+            year: media.season_year,
+            episodes: media.episodes,
+            started_at,
+        })
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/schedule_query.graphql"
+)]
+struct ScheduleQuery;
+
+impl ScheduleQuery {
+    async fn get(anilist: &Anilist, id: UserId) -> Option<Vec<Airing>> {
+        let id = Some(id.into());
+        let query = ScheduleQuery::build_query(schedule_query::Variables { id });
+        let response = anilist.post(&query).await.ok()?;
+        let json = response
+            .json::<Response<schedule_query::ResponseData>>()
+            .await
+            .ok()?;
+
+        let mut schedule: Vec<_> = json
+            .data?
+            .media_list_collection?
+            .lists?
+            .into_iter()
+            .flatten()
+            .flat_map(|list| list.entries.unwrap_or_default())
+            .flatten()
+            .filter_map(|entry| entry.media)
+            .flat_map(|media| {
+                let title = media
+                    .title
+                    .and_then(|t| Titles::from(t).get(anilist.language))
+                    .unwrap_or_default();
+                let nodes = media
+                    .airing_schedule
+                    .and_then(|s| s.nodes)
+                    .unwrap_or_default();
+
+                nodes.into_iter().flatten().map(move |node| Airing {
+                    id: media.id,
+                    title: title.clone(),
+                    episode: node.episode,
+                    airing_at: node.airing_at,
+                    time_until: node.time_until_airing,
+                    duration: media.duration,
+                })
+            })
+            .collect();
+
+        schedule.sort_by_key(|a| a.airing_at);
+
+        Some(schedule)
+    }
+}
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/notifications_query.graphql"
+)]
+struct NotificationsQuery;
+
+impl NotificationsQuery {
+    /// Pages read at most, when looking for the notifications since a time
+    const MAX_PAGES: i64 = 10;
+
+    /// The airing notifications created after the given time, newest first,
+    /// or the latest page of them. Reading them doesn't mark them as read.
+    async fn get(anilist: &Anilist, since: Option<i64>) -> Option<Vec<AiredEpisode>> {
+        let mut aired = Vec::new();
+        for page in 1..=Self::MAX_PAGES {
+            let variables = notifications_query::Variables {
+                page: Some(page),
+                reset: Some(false),
+            };
+            let response = anilist.post(&Self::build_query(variables)).await.ok()?;
+            let json = response
+                .json::<Response<notifications_query::ResponseData>>()
+                .await
+                .ok()?;
+            let page = json.data?.page?;
+
+            let notifications: Vec<_> = page
+                .notifications
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter_map(|notification| {
+                    match notification {
+                    notifications_query::NotificationsQueryPageNotifications::AiringNotification(
+                        n,
+                    ) => Some(n),
+                    _ => None,
+                }
+                })
+                .collect();
+            let has_older = notifications
+                .last()
+                .is_some_and(|n| since.is_some_and(|t| n.created_at.unwrap_or_default() > t));
+
+            aired.extend(
+                notifications
+                    .into_iter()
+                    .filter(|n| since.is_none_or(|t| n.created_at.unwrap_or_default() > t))
+                    .filter_map(|n| Self::aired_episode(anilist, n)),
+            );
+
+            let has_next = page
+                .page_info
+                .and_then(|i| i.has_next_page)
+                .unwrap_or(false);
+            if !(has_older && has_next) {
+                break;
+            }
+        }
+
+        Some(aired)
+    }
+
+    fn aired_episode(
+        anilist: &Anilist,
+        notification: notifications_query::NotificationsQueryPageNotificationsOnAiringNotification,
+    ) -> Option<AiredEpisode> {
+        let media = notification.media?;
+        let mut titles = Titles::from(media.title?).ordered(anilist.language);
+        if titles.is_empty() {
+            return None;
+        }
+        let title = titles.remove(0);
+        let synonyms = titles
+            .into_iter()
+            .chain(media.synonyms.into_iter().flatten().flatten())
+            .collect();
+
+        Some(AiredEpisode {
+            episode: notification.episode,
+            created_at: notification.created_at.unwrap_or_default(),
+            media: MediaInfo {
+                id: media.id,
+                title,
+                synonyms,
+                format: None,
+                year: None,
+                episodes: media.episodes,
+                started_at: None,
+            },
+        })
+    }
+
+    /// Clears the unread count of the user, for every kind of notification.
+    async fn reset(anilist: &Anilist) -> Result<()> {
+        let variables = notifications_query::Variables {
+            page: Some(1),
+            reset: Some(true),
+        };
+        anilist
+            .post(&Self::build_query(variables))
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, From, Into)]
+struct UserId(i64);
+
+#[derive(GraphQLQuery, Debug)]
+#[graphql(
+    schema_path = "schema/anilist_schema.json",
+    query_path = "schema/user_query.graphql"
+)]
+struct UserQuery;
+
+impl UserQuery {
+    /// The user of the token, the cached one unless it has to be checked.
+    async fn get(anilist: &Anilist, cached: bool) -> Result<Viewer> {
+        let query = UserQuery::build_query(user_query::Variables);
+        let json: Response<user_query::ResponseData> = if cached {
+            anilist.fetch(Kind::Viewer, &query).await?
+        } else {
+            anilist
+                .post(&query)
+                .await?
+                .error_for_status()?
+                .json()
+                .await?
+        };
+
+        let viewer = json.data.and_then(|d| d.viewer).context("no viewer")?;
+
+        let score_format = viewer
+            .media_list_options
+            .and_then(|o| o.score_format)
+            .map(ScoreFormat::from)
+            .unwrap_or_default();
+
+        Ok(Viewer {
+            id: viewer.id.into(),
+            name: viewer.name,
+            score_format,
+        })
+    }
+}
+
+/// The user authenticated by the token.
+#[derive(Debug)]
+pub struct Viewer {
+    id: UserId,
+    name: String,
+    score_format: ScoreFormat,
+}
+
+impl Viewer {
+    pub fn id(&self) -> i64 {
+        self.id.into()
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn score_format(&self) -> ScoreFormat {
+        self.score_format
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct Anilist {
+    /// Locked while a new token is asked, the other requests wait for it
+    client: tokio::sync::Mutex<Client>,
+    client_id: AnilistId,
+    /// GraphQL server the requests are sent to, AniList when unset
+    endpoint: Option<String>,
+    /// Keeps the cached responses of each user apart
+    scope: Mutex<String>,
+    language: TitleLanguage,
+    rate_limit: RateLimit,
+}
+
+impl Anilist {
+    pub fn new(client_id: Option<AnilistId>) -> Result<Self> {
+        let client_id = client_id.unwrap_or_default();
+        let token = config::load("anilist", "token").or_else(|_| oauth_token(client_id))?;
+
+        Self {
+            client_id,
+            ..Self::anonymous()?
+        }
+        .with_token(&token)
+    }
+
+    /// Client without a token, for the queries of public data (eg. search).
+    pub fn anonymous() -> Result<Self> {
+        let anilist = Self {
+            client: tokio::sync::Mutex::new(build_client(None)?),
+            language: TitleLanguage::load()?,
+            ..Default::default()
+        };
+
+        match config::load("anilist", "endpoint") {
+            Ok(endpoint) => Ok(anilist.with_endpoint(endpoint)),
+            Err(_) => Ok(anilist),
+        }
+    }
+
+    /// Sends the requests to another GraphQL server, eg. a proxy or a mock.
+    pub fn with_endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = Some(endpoint.into());
+        self
+    }
+
+    /// Sends the requests on behalf of the user the token belongs to.
+    pub fn with_token(mut self, token: &str) -> Result<Self> {
+        *self.client.get_mut() = build_client(Some(token))?;
+        self.set_scope(token)?;
+        Ok(self)
+    }
+
+    /// Asks a new token and uses it for the next requests. The requests
+    /// rejected together ask it once, the others use the new one.
+    async fn login(&self, rejected_scope: &str) -> Result<()> {
+        let mut client = self.client.lock().await;
+        if self.scope()? != rejected_scope {
+            return Ok(());
+        }
+
+        eprintln!("The AniList token is invalid or expired, please log in again");
+
+        // the prompt blocks on the terminal input
+        let client_id = self.client_id;
+        let token = tokio::task::spawn_blocking(move || oauth_token(client_id)).await??;
+
+        *client = build_client(Some(&token))?;
+        self.set_scope(&token)
+    }
+
+    fn set_scope(&self, token: &str) -> Result<()> {
+        *self
+            .scope
+            .lock()
+            .map_err(|_| anyhow!("Unable to create client"))? = cache::scope(Some(token));
+
+        Ok(())
+    }
+
+    fn endpoint(&self) -> &str {
+        self.endpoint.as_deref().unwrap_or(ENDPOINT)
+    }
+
+    /// Checks the token, returning the user it belongs to.
+    pub async fn get_viewer(&self) -> Result<Viewer> {
+        UserQuery::get(self, false).await
+    }
+
+    /// Sends a query, reading the response from the cache while it is fresh.
+    async fn fetch<Q: Serialize, R: DeserializeOwned>(
+        &self,
+        kind: Kind,
+        query: &Q,
+    ) -> Result<Response<R>> {
+        let key = format!(
+            "{}{}{}",
+            self.endpoint(),
+            self.scope()?,
+            serde_json::to_string(query)?
+        );
+        if let Some(body) = cache::get(kind, &key)
+            && let Ok(json) = serde_json::from_str(&body)
+        {
+            return Ok(json);
+        }
+
+        let body = self.post(query).await?.text().await?;
+        let json: Response<R> = serde_json::from_str(&body)?;
+        if json.data.is_some() && json.errors.is_none() {
+            cache::put(kind, &key, &body);
+        }
+
+        Ok(json)
+    }
+
+    fn scope(&self) -> Result<String> {
+        let scope = self
+            .scope
+            .lock()
+            .map_err(|_| anyhow!("Unable to read the cache scope"))?;
+
+        Ok(scope.clone())
+    }
+
+    /// Sends a query, when the token is rejected a new one is asked and the
+    /// query is sent again.
+    async fn post<Q: Serialize>(&self, query: &Q) -> Result<reqwest::Response> {
+        let scope = self.scope()?;
+        let response = self.send(query).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        self.login(&scope).await?;
+
+        let response = self.send(query).await?;
+        ensure!(
+            response.status() != StatusCode::UNAUTHORIZED,
+            RequestError::Unauthorized
+        );
+
+        Ok(response)
+    }
+
+    /// Sends a query within the rate limit, waiting and sending it again
+    /// when the limit is exceeded.
+    async fn send<Q: Serialize>(&self, query: &Q) -> Result<reqwest::Response> {
+        for _ in 0..RateLimit::MAX_RETRIES {
+            if let Some(delay) = self.rate_limit.delay() {
+                tokio::time::sleep(delay).await;
+            }
+
+            let response = self
+                .client()
+                .await
+                .post(self.endpoint())
+                .json(query)
+                .send()
+                .await?;
+            self.rate_limit.update(response.headers());
+
+            let Some(wait) = retry_after(response.status(), response.headers()) else {
+                return Ok(response);
+            };
+
+            eprintln!(
+                "AniList rate limit reached, retrying in {}s",
+                wait.as_secs()
+            );
+            tokio::time::sleep(wait).await;
+        }
+
+        bail!(RequestError::RateLimit)
+    }
+
+    async fn client(&self) -> Client {
+        self.client.lock().await.clone()
+    }
+
+    /// MyAnimeList ids of the given anime, missing the ones without one.
+    pub async fn get_mal_ids(&self, ids: &[AnimeId]) -> HashMap<AnimeId, u32> {
+        if ids.is_empty() {
+            return HashMap::new();
+        }
+
+        let ids = ids.iter().map(|&id| Some(id.into())).collect();
+        let pairs = MappingQuery::get(self, Some(ids), None).await;
+
+        pairs.into_iter().flatten().collect()
+    }
+
+    /// AniList ids of the given MyAnimeList anime, missing the ones without one.
+    pub async fn get_anilist_ids(&self, mal_ids: &[u32]) -> HashMap<u32, AnimeId> {
+        if mal_ids.is_empty() {
+            return HashMap::new();
+        }
+
+        let mal_ids = mal_ids.iter().map(|&id| Some(id.into())).collect();
+        let pairs = MappingQuery::get(self, None, Some(mal_ids)).await;
+
+        pairs
+            .into_iter()
+            .flatten()
+            .map(|(id, mal)| (mal, id))
+            .collect()
+    }
+
+    /// Anime in a list of the user, by status (eg. `Planning`) or custom one.
+    pub async fn get_named_list(&self, name: &str) -> Option<Vec<WatchingAnime>> {
+        let user_id = UserQuery::get(self, true).await.ok()?.id;
+
+        WatchingQuery::get(self, user_id, Some(name)).await
+    }
+
+    /// Entries of the user list, only the ones with the given status when set.
+    pub async fn get_list(&self, status: Option<ListStatus>) -> Option<Vec<ListEntry>> {
+        let user_id = UserQuery::get(self, true).await.ok()?.id;
+
+        ListQuery::get(self, user_id, status).await
+    }
+
+    /// Upcoming episodes of the watching list, sorted by release time.
+    pub async fn get_schedule(&self) -> Option<Vec<Airing>> {
+        let user_id = UserQuery::get(self, true).await.ok()?.id;
+
+        ScheduleQuery::get(self, user_id).await
+    }
+
+    /// Episodes notified after the given time, newest first, or the latest
+    /// ones without a time.
+    pub async fn get_notifications(&self, since: Option<i64>) -> Option<Vec<AiredEpisode>> {
+        NotificationsQuery::get(self, since).await
+    }
+
+    /// Clears the unread notifications badge of AniList, social ones included.
+    pub async fn mark_notifications_read(&self) -> Result<()> {
+        NotificationsQuery::reset(self).await
+    }
+
+    pub async fn search(&self, query: &str) -> Option<Vec<MediaInfo>> {
+        SearchQuery::get(self, query).await
+    }
+
+    /// Sequels, prequels and side stories of an anime, sequels first.
+    pub async fn get_relations(&self, id: AnimeId) -> Option<Vec<RelatedMedia>> {
+        RelationsQuery::get(self, id)
+            .await
+            .map(|(_, related)| related)
+    }
+
+    /// The anime and all the ones reached through their sequels, prequels and
+    /// side stories, sorted by release date. The anime whose relations can't
+    /// be requested are kept, without following them.
+    pub async fn get_franchise(&self, id: AnimeId) -> Option<Vec<MediaInfo>> {
+        let (media, mut related) = RelationsQuery::get(self, id).await?;
+
+        let mut franchise = vec![media];
+        let mut visited = HashSet::from([id]);
+        let mut queue = VecDeque::new();
+        let (mut failed, mut truncated) = (0, false);
+        loop {
+            for r in related {
+                if visited.contains(&r.media.id()) {
+                    continue;
+                }
+                if visited.len() >= MAX_FRANCHISE {
+                    truncated = true;
+                    break;
+                }
+                visited.insert(r.media.id());
+                queue.push_back(r.media);
+            }
+
+            let Some(media) = queue.pop_front() else {
+                break;
+            };
+            related = match RelationsQuery::get(self, media.id()).await {
+                Some((_, related)) => related,
+                None => {
+                    failed += 1;
+                    Vec::new()
+                }
+            };
+            franchise.push(media);
+        }
+        franchise.sort_by_key(|m| (m.started_at.is_none(), m.started_at, m.id));
+
+        if failed > 0 {
+            eprintln!(
+                "Unable to get the relations of {failed} anime, the franchise may be incomplete"
+            );
+        }
+        if truncated {
+            eprintln!(
+                "The franchise has more than {MAX_FRANCHISE} anime, only the first ones are kept"
+            );
+        }
+
+        Some(franchise)
+    }
+
+    /// Changes an entry of the user list, adding the anime when missing.
+    pub async fn set_entry(&self, id: AnimeId, update: EntryUpdate) -> Result<()> {
+        ProgressMutation::put(self, id, update).await
+    }
 
-        let episodes = media.episodes?;
-        let progress = media.media_list_entry?.progress?;
+    /// Removes an anime from the user list.
+    pub async fn remove(&self, id: AnimeId) -> Result<()> {
+        let entry_id = EntryQuery::get(self, id)
+            .await
+            .ok_or(anyhow!("anime {id} is not in your list"))?;
 
-        Some(Progress { episodes, progress })
+        DeleteMutation::delete(self, entry_id).await
     }
-}
 
-#[derive(GraphQLQuery, Debug)]
-#[graphql(
-    schema_path = "schema/anilist_schema.json",
-    query_path = "schema/progress_mutation.graphql"
-)]
-struct ProgressMutation;
+    /// Sends the pending updates of the journal and returns how many were synced.
+    /// Updates behind the remote progress are dropped, so it never goes backwards.
+    pub async fn sync_journal(&self) -> Result<usize> {
+        let mut journal = Journal::load();
+        if journal.is_empty() {
+            return Ok(0);
+        }
+
+        // without the current progress the updates could move it backwards,
+        // they stay in the journal
+        let ids: Vec<_> = journal.entries().map(|e| e.id()).collect();
+        let progress = ProgressBatchQuery::get(self, &ids).await?;
+
+        let entries = journal.take();
+
+        let mut synced = 0;
+        for entry in entries {
+            let (id, number) = (entry.id(), entry.progress());
+            let (status, repeat) = match progress.get(&id) {
+                Some(p) if p.has_seen(number) => continue,
+                Some(p) if p.is_last(number) => (ListStatus::Completed, p.repeat_after(number)),
+                None | Some(_) => (entry.status(), None),
+            };
+
+            let update = EntryUpdate {
+                status: Some(status),
+                progress: Some(number),
+                repeat,
+                ..Default::default()
+            }
+            .with_review(entry.review().clone());
+
+            match ProgressMutation::put(self, id, update).await {
+                Ok(()) => synced += 1,
+                Err(_) => journal.push(entry),
+            }
+        }
+
+        journal.save()?;
+
+        Ok(synced)
+    }
 
-impl ProgressMutation {
-    async fn put(
-        client: &Client,
+    /// Sends an update, keeping it in the journal when AniList is unreachable.
+    async fn save(
+        &self,
         id: AnimeId,
-        progress: EpisodeId,
-        status: progress_mutation::MediaListStatus,
+        number: EpisodeId,
+        status: ListStatus,
+        repeat: Option<i64>,
+        review: Review,
     ) -> Result<()> {
-        let variables = progress_mutation::Variables {
+        let update = EntryUpdate {
             status: Some(status),
-            id: Some(id.into()),
-            progress: Some(progress.into()),
-        };
-
-        let query = ProgressMutation::build_query(variables);
-        client
-            .post(ENDPOINT)
-            .json(&query)
-            .send()
-            .await?
-            .error_for_status()?;
-
-        Ok(())
+            progress: Some(number),
+            repeat,
+            ..Default::default()
+        }
+        .with_review(review.clone());
+
+        match ProgressMutation::put(self, id, update).await {
+            Err(err) if is_offline(&err) => {
+                let mut journal = Journal::load();
+                journal.push(Entry::new(id, number, status, review));
+                journal.save()?;
+
+                eprintln!("AniList is unreachable, the progress will be synced later");
+
+                Ok(())
+            }
+            Err(err) => Err(err),
+            Ok(()) => {
+                self.sync_journal().await.ok();
+
+                Ok(())
+            }
+        }
     }
 }
 
-#[derive(GraphQLQuery, Debug)]
-#[graphql(
-    schema_path = "schema/anilist_schema.json",
-    query_path = "schema/watching_query.graphql"
-)]
-struct WatchingQuery;
-
-impl WatchingQuery {
-    async fn get(client: &Client, id: UserId) -> Option<Vec<WatchingAnime>> {
-        let id = Some(id.into());
-        let variables = watching_query::Variables { id };
-        let query = WatchingQuery::build_query(variables);
-        let response = client.post(ENDPOINT).json(&query).send().await.ok()?;
-        let json = response
-            .json::<Response<watching_query::ResponseData>>()
-            .await
-            .ok()?;
+impl Tracker for Anilist {
+    async fn get_watching_list(&self) -> Option<Vec<WatchingAnime>> {
+        let user_id = UserQuery::get(self, true).await.ok()?.id;
+        let list = WatchingQuery::get(self, user_id, None).await?;
 
-        let collection = json
-            .data?
-            .media_list_collection?
-            .lists?
-            .get_mut(0)?
-            .take()?;
+        // AniList is reachable again, send the pending updates
+        self.sync_journal().await.ok();
 
-        let mut list: Vec<_> = collection
-            .entries?
-            .into_iter()
-            .filter_map(|collection| {
-                let progress = collection.as_ref()?.progress?;
-                let media = collection?.media?;
+        Some(list)
+    }
 
-                let watched = match media.next_airing_episode {
-                    Some(airing) => airing.episode - (progress + 1),
-                    None => media.episodes? - progress,
-                };
+    async fn get_progress(&self, id: AnimeId) -> Option<Progress> {
+        ProgressQuery::get(self, id).await
+    }
 
-                let title = media.title?.romaji?;
-                let id = media.id;
+    /// Progress of many anime with a single request, missing the ones not in the list.
+    async fn get_progress_batch(&self, ids: &[AnimeId]) -> HashMap<AnimeId, Progress> {
+        if ids.is_empty() {
+            return HashMap::new();
+        }
 
-                Some(WatchingAnime { id, title, watched })
+        // the anime would look out of the list, the missing progress is told
+        ProgressBatchQuery::get(self, ids)
+            .await
+            .unwrap_or_else(|err| {
+                eprintln!("Unable to get the progress from AniList: {err}");
+                HashMap::new()
             })
-            .collect();
+    }
 
-        list.sort_by(|a, b| a.title.cmp(&b.title));
+    async fn update(&mut self, id: AnimeId, number: EpisodeId) -> Result<()> {
+        let (status, repeat) = match self.get_progress(id).await {
+            Some(p) => match p.status_after(number) {
+                Some(status) => (status, p.repeat_after(number)),
+                None => return Ok(()),
+            },
+            None => (ListStatus::Current, None),
+        };
 
-        Some(list)
+        self.save(id, number, status, repeat, Review::default())
+            .await
     }
-}
-
-#[derive(Clone, Copy, Debug, From, Into)]
-struct UserId(i64);
 
-#[derive(GraphQLQuery, Debug)]
-#[graphql(
-    schema_path = "schema/anilist_schema.json",
-    query_path = "schema/user_query.graphql"
-)]
-struct UserQuery;
+    /// Marks the anime as completed at the given episode, with an optional review.
+    async fn complete(&mut self, id: AnimeId, number: EpisodeId, review: Review) -> Result<()> {
+        let progress = self.get_progress(id).await;
+        let repeat = progress.and_then(|p| p.repeat_after(number));
 
-impl UserQuery {
-    async fn get(client: &Client) -> Option<UserId> {
-        let query = UserQuery::build_query(user_query::Variables);
-        let response = client.post(ENDPOINT).json(&query).send().await.ok()?;
-        let json = response
-            .json::<Response<user_query::ResponseData>>()
+        self.save(id, number, ListStatus::Completed, repeat, review)
             .await
-            .ok()?;
+    }
+
+    async fn rewatch(&mut self, id: AnimeId) -> Result<()> {
+        let update = EntryUpdate {
+            status: Some(ListStatus::Repeating),
+            progress: Some(EpisodeId(0)),
+            ..Default::default()
+        };
+
+        self.set_entry(id, update).await
+    }
 
-        json.data?.viewer.map(|d| d.id.into())
+    async fn score_format(&self) -> ScoreFormat {
+        UserQuery::get(self, true)
+            .await
+            .map(|viewer| viewer.score_format)
+            .unwrap_or_default()
     }
 }
 
-#[derive(Default, Debug)]
-pub struct Anilist {
-    client: Client,
+/// Requests left in the current window of the AniList rate limit.
+#[derive(Debug)]
+struct RateLimit {
+    remaining: AtomicU32,
 }
 
-impl Anilist {
-    pub fn new(client_id: Option<AnilistId>) -> Result<Self> {
-        let client_id = client_id.unwrap_or(AnilistId(4047));
-        let token = config::load("anilist", "token").or_else(|_| oauth_token(client_id))?;
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            remaining: AtomicU32::new(u32::MAX),
+        }
+    }
+}
 
-        let mut headers = header::HeaderMap::new();
-        headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
-        headers.insert(
-            header::AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {token}"))
-                .map_err(|_| anyhow!("Invalid token"))?,
-        );
-        headers.insert(
-            header::CONTENT_TYPE,
-            HeaderValue::from_static("application/json"),
-        );
+impl RateLimit {
+    const MAX_RETRIES: usize = 3;
+    /// Remaining requests under which they are spaced out
+    const LOW_REMAINING: u32 = 5;
+    /// Pause between requests when few are left, to spread them in the window
+    const THROTTLE: Duration = Duration::from_secs(2);
+
+    fn update(&self, headers: &HeaderMap) {
+        if let Some(remaining) = header_value(headers, "x-ratelimit-remaining") {
+            self.remaining.store(remaining, Ordering::Relaxed);
+        }
+    }
 
-        let client = Client::builder()
-            .default_headers(headers)
-            .build()
-            .map_err(|_| anyhow!("Unable to create client"))?;
+    fn delay(&self) -> Option<Duration> {
+        let remaining = self.remaining.load(Ordering::Relaxed);
 
-        Ok(Self { client })
+        (remaining < Self::LOW_REMAINING).then_some(Self::THROTTLE)
     }
+}
 
-    pub async fn get_watching_list(&self) -> Option<Vec<WatchingAnime>> {
-        let user_id = UserQuery::get(&self.client).await?;
-        let list = WatchingQuery::get(&self.client, user_id).await?;
+/// Time to wait before sending again a request rejected for the rate limit.
+fn retry_after(status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+    const DEFAULT_WAIT: u64 = 60;
 
-        Some(list)
+    if status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
     }
 
-    pub async fn get_progress(&self, id: AnimeId) -> Option<Progress> {
-        ProgressQuery::get(&self.client, id).await
-    }
+    let seconds = header_value(headers, header::RETRY_AFTER.as_str()).unwrap_or(DEFAULT_WAIT);
 
-    pub async fn update(&mut self, id: AnimeId, number: EpisodeId) -> Result<()> {
-        use progress_mutation::MediaListStatus;
+    Some(Duration::from_secs(seconds))
+}
 
-        let progress = self.get_progress(id).await;
-        let status = match progress {
-            Some(p) if p.has_seen(number) => return Ok(()),
-            Some(p) if p.is_last(number) => MediaListStatus::COMPLETED,
-            None | Some(_) => MediaListStatus::CURRENT,
-        };
+fn header_value<T: FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
 
-        ProgressMutation::put(&self.client, id, number, status).await?;
+/// Checks if a request failed because AniList could not be reached.
+fn is_offline(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<reqwest::Error>().is_some_and(|e| {
+        e.is_connect()
+            || e.is_timeout()
+            || e.is_request()
+            || e.status().is_some_and(|s| s.is_server_error())
+    })
+}
 
-        Ok(())
+fn build_client(token: Option<&str>) -> Result<Client> {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(header::ACCEPT, HeaderValue::from_static("application/json"));
+    if let Some(token) = token {
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&format!("Bearer {token}"))
+                .map_err(|_| anyhow!("Invalid token"))?,
+        );
     }
+    headers.insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+
+    Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|_| anyhow!("Unable to create client"))
+}
+
+/// Expiration date of a token, read from its JWT payload.
+pub fn token_expiry(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    let exp = claims.get("exp")?.as_f64()?;
+
+    DateTime::from_timestamp(exp as i64, 0)
 }
 
 fn oauth_token(client_id: AnilistId) -> Result<String> {
@@ -269,6 +2056,9 @@ fn oauth_token(client_id: AnilistId) -> Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock::{self, MockServer};
+    use serde_json::json;
+    use serial_test::file_serial;
     use simple_test_case::test_case;
 
     #[test_case("42", 42; "valid id")]
@@ -303,6 +2093,8 @@ mod tests {
             watched,
             id: 1,
             title: "Test".into(),
+            alternatives: vec![],
+            lists: vec![],
         };
         assert_eq!(anime.watched(), expected);
     }
@@ -316,6 +2108,8 @@ mod tests {
             watched: 0,
             id: 1,
             title: title.into(),
+            alternatives: vec![],
+            lists: vec![],
         };
         assert_eq!(anime.title(), expected);
     }
@@ -328,6 +2122,8 @@ mod tests {
             watched: 0,
             id,
             title: "Test".into(),
+            alternatives: vec![],
+            lists: vec![],
         };
         assert_eq!(anime.id(), AnimeId(expected));
     }
@@ -340,6 +2136,8 @@ mod tests {
         let p = Progress {
             episodes: 12,
             progress,
+            title: String::new(),
+            ..Default::default()
         };
         assert_eq!(p.latest(), EpisodeId(expected));
     }
@@ -353,6 +2151,8 @@ mod tests {
         let p = Progress {
             episodes: 12,
             progress,
+            title: String::new(),
+            ..Default::default()
         };
         assert_eq!(p.has_seen(EpisodeId(ep)), expected);
     }
@@ -366,6 +2166,8 @@ mod tests {
         let p = Progress {
             episodes,
             progress: 0,
+            title: String::new(),
+            ..Default::default()
         };
         assert_eq!(p.is_last(EpisodeId(ep)), expected);
     }
@@ -396,22 +2198,351 @@ mod tests {
         let p = Progress {
             episodes: 12,
             progress,
+            title: String::new(),
+            ..Default::default()
         };
         assert_eq!(p.has_seen(EpisodeId(ep)), expected);
     }
 
     #[test_case(1, 1, true; "single episode is last")]
-    #[test_case(0, 0, true; "zero episodes zero is last")]
+    #[test_case(0, 0, false; "unknown episodes never last")]
+    #[test_case(0, 12, false; "unknown episodes after the first")]
     #[test_case(100, 50, false; "well before last")]
     #[test]
     fn test_progress_is_last_edge(episodes: i64, ep: u32, expected: bool) {
         let p = Progress {
             episodes,
             progress: 0,
+            title: String::new(),
+            ..Default::default()
         };
         assert_eq!(p.is_last(EpisodeId(ep)), expected);
     }
 
+    #[test_case(Some(ListStatus::Completed), 12, 1, true; "completed series")]
+    #[test_case(Some(ListStatus::Repeating), 5, 3, true; "rewatch past the episode")]
+    #[test_case(Some(ListStatus::Repeating), 2, 3, false; "rewatch in progress")]
+    #[test_case(Some(ListStatus::Current), 5, 3, false; "watching")]
+    #[test_case(None, 0, 1, false; "unknown status")]
+    #[test]
+    fn test_progress_starts_rewatch(
+        status: Option<ListStatus>,
+        progress: i64,
+        ep: u32,
+        expected: bool,
+    ) {
+        let p = Progress::new(12, progress, String::new()).with_status(status, 0);
+        assert_eq!(p.starts_rewatch(EpisodeId(ep)), expected);
+    }
+
+    #[test_case(Some(ListStatus::Current), 3, 4, Some(ListStatus::Current), None; "next episode")]
+    #[test_case(Some(ListStatus::Current), 3, 2, None, None; "already seen")]
+    #[test_case(Some(ListStatus::Current), 11, 12, Some(ListStatus::Completed), None; "last episode")]
+    #[test_case(Some(ListStatus::Repeating), 0, 1, Some(ListStatus::Repeating), None; "rewatching")]
+    #[test_case(Some(ListStatus::Repeating), 11, 12, Some(ListStatus::Completed), Some(2); "rewatch finished")]
+    #[test]
+    fn test_progress_status_after(
+        status: Option<ListStatus>,
+        progress: i64,
+        ep: u32,
+        expected: Option<ListStatus>,
+        repeat: Option<i64>,
+    ) {
+        let p = Progress::new(12, progress, String::new()).with_status(status, 1);
+        assert_eq!(p.status_after(EpisodeId(ep)), expected);
+        assert_eq!(p.repeat_after(EpisodeId(ep)), repeat);
+    }
+
+    #[test_case(ListStatus::Current, "\"CURRENT\""; "current")]
+    #[test_case(ListStatus::Completed, "\"COMPLETED\""; "completed")]
+    #[test]
+    fn test_list_status_serialize(status: ListStatus, expected: &str) {
+        assert_eq!(serde_json::to_string(&status).unwrap(), expected);
+    }
+
+    #[test_case(ListStatus::Current, "Watching"; "current")]
+    #[test_case(ListStatus::Repeating, "Rewatching"; "repeating")]
+    #[test_case(ListStatus::Planning, "Planning"; "planning")]
+    #[test]
+    fn test_list_status_display(status: ListStatus, expected: &str) {
+        assert_eq!(status.to_string(), expected);
+    }
+
+    #[test_case(list_query::MediaListStatus::PAUSED, ListStatus::Paused; "paused")]
+    #[test_case(list_query::MediaListStatus::REPEATING, ListStatus::Repeating; "repeating")]
+    #[test]
+    fn test_list_status_try_from(status: list_query::MediaListStatus, expected: ListStatus) {
+        assert_eq!(ListStatus::try_from(status).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_list_status_try_from_unknown() {
+        let status = list_query::MediaListStatus::Other("WATCHED".into());
+        assert!(ListStatus::try_from(status).is_err());
+    }
+
+    fn list_entry() -> ListEntry {
+        ListEntry {
+            id: 1,
+            mal_id: None,
+            title: "Test".into(),
+            status: ListStatus::Planning,
+            progress: 0,
+            episodes: None,
+            score: 0.0,
+            rating: 0.0,
+            repeat: 0,
+            notes: None,
+            started_at: None,
+            completed_at: None,
+        }
+    }
+
+    #[test_case(3, Some(12), "3/12"; "finished series")]
+    #[test_case(5, None, "5/?"; "airing series")]
+    #[test]
+    fn test_list_entry_progress(progress: i64, episodes: Option<i64>, expected: &str) {
+        let entry = ListEntry {
+            id: 1,
+            title: "Test".into(),
+            status: ListStatus::Current,
+            progress,
+            episodes,
+            ..list_entry()
+        };
+        assert_eq!(entry.progress(), expected);
+    }
+
+    #[test_case(0.0, None; "unrated")]
+    #[test_case(7.5, Some(7.5); "rated")]
+    #[test]
+    fn test_list_entry_score(score: f64, expected: Option<f64>) {
+        let entry = ListEntry {
+            id: 1,
+            title: "Test".into(),
+            status: ListStatus::Completed,
+            progress: 12,
+            episodes: Some(12),
+            score,
+            ..list_entry()
+        };
+        assert_eq!(entry.score(), expected);
+    }
+
+    #[test_case(search_query::MediaFormat::TV_SHORT, Some("TV Short"); "tv short")]
+    #[test_case(search_query::MediaFormat::MOVIE, Some("Movie"); "movie")]
+    #[test_case(search_query::MediaFormat::MANGA, None; "not an anime")]
+    #[test]
+    fn test_format_name(format: search_query::MediaFormat, expected: Option<&str>) {
+        assert_eq!(format.name().as_deref(), expected);
+    }
+
+    #[test_case(ScoreFormat::Point100, "85", Some(85.0); "hundred points")]
+    #[test_case(ScoreFormat::Point100, "101", None; "hundred points out of scale")]
+    #[test_case(ScoreFormat::Point10Decimal, "7.25", Some(7.3); "one decimal rounded")]
+    #[test_case(ScoreFormat::Point10Decimal, "-1", None; "negative decimal")]
+    #[test_case(ScoreFormat::Point10, "8", Some(8.0); "ten points")]
+    #[test_case(ScoreFormat::Point10, "7.5", None; "ten points without decimals")]
+    #[test_case(ScoreFormat::Point5, "4", Some(4.0); "stars as number")]
+    #[test_case(ScoreFormat::Point5, "★★★", Some(3.0); "stars")]
+    #[test_case(ScoreFormat::Point5, "******", None; "too many stars")]
+    #[test_case(ScoreFormat::Point3, ":)", Some(3.0); "smiley")]
+    #[test_case(ScoreFormat::Point3, "2", Some(2.0); "smiley as number")]
+    #[test_case(ScoreFormat::Point3, ":D", None; "unknown smiley")]
+    #[test]
+    fn test_score_format_parse(format: ScoreFormat, input: &str, expected: Option<f64>) {
+        assert_eq!(format.parse(input), expected);
+    }
+
+    #[test]
+    fn test_entry_update_with_review() {
+        let update = EntryUpdate {
+            score: Some(9.0),
+            ..Default::default()
+        };
+        let review = Review {
+            score: Some(6.0),
+            notes: Some("Great ending".into()),
+            completed_at: NaiveDate::from_ymd_opt(2025, 10, 20),
+        };
+
+        let update = update.with_review(review);
+        assert_eq!(update.score, Some(9.0));
+        assert_eq!(update.notes.as_deref(), Some("Great ending"));
+        assert_eq!(update.completed_at, NaiveDate::from_ymd_opt(2025, 10, 20));
+    }
+
+    fn media_info() -> MediaInfo {
+        MediaInfo {
+            id: 154587,
+            title: "Sousou no Frieren".into(),
+            synonyms: vec![
+                "Frieren: Beyond Journey's End".into(),
+                "葬送のフリーレン".into(),
+            ],
+            format: Some("TV".into()),
+            year: Some(2023),
+            episodes: Some(28),
+            started_at: None,
+        }
+    }
+
+    #[test_case("sousou no frieren", true; "romaji title ignoring case")]
+    #[test_case(" Frieren: Beyond Journey's End ", true; "english title with spaces")]
+    #[test_case("葬送のフリーレン", true; "native title")]
+    #[test_case("frieren", false; "partial title")]
+    #[test]
+    fn test_media_info_matches(query: &str, expected: bool) {
+        assert_eq!(media_info().matches(query), expected);
+    }
+
+    #[test]
+    fn test_media_info_display() {
+        let mut media = media_info();
+        assert_eq!(media.to_string(), "Sousou no Frieren (TV, 2023)");
+
+        media.format = None;
+        media.year = None;
+        assert_eq!(media.to_string(), "Sousou no Frieren");
+    }
+
+    #[test_case(Format::Tv, true; "tv")]
+    #[test_case(Format::Ova, false; "other format")]
+    #[test]
+    fn test_format_matches(format: Format, expected: bool) {
+        assert_eq!(format.matches(&media_info()), expected);
+    }
+
+    #[test_case(Relation::Sequel, "Sequel"; "sequel")]
+    #[test_case(Relation::SideStory, "Side story"; "side story")]
+    #[test]
+    fn test_relation_display(relation: Relation, expected: &str) {
+        assert_eq!(relation.to_string(), expected);
+    }
+
+    #[test]
+    fn test_relation_order() {
+        let mut relations = [Relation::SideStory, Relation::Prequel, Relation::Sequel];
+        relations.sort();
+
+        assert_eq!(
+            relations,
+            [Relation::Sequel, Relation::Prequel, Relation::SideStory]
+        );
+    }
+
+    #[test_case("romaji", TitleLanguage::Romaji; "romaji")]
+    #[test_case(" English ", TitleLanguage::English; "english mixed case")]
+    #[test_case("native", TitleLanguage::Native; "native")]
+    #[test]
+    fn test_title_language_from_str(s: &str, expected: TitleLanguage) {
+        assert_eq!(s.parse::<TitleLanguage>().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_title_language_from_str_err() {
+        assert!("italian".parse::<TitleLanguage>().is_err());
+    }
+
+    fn titles(english: Option<&str>) -> Titles {
+        Titles {
+            romaji: Some("Sousou no Frieren".into()),
+            english: english.map(String::from),
+            native: Some("葬送のフリーレン".into()),
+        }
+    }
+
+    #[test_case(
+        TitleLanguage::Romaji,
+        Some("Frieren"),
+        &["Sousou no Frieren", "Frieren", "葬送のフリーレン"];
+        "romaji first"
+    )]
+    #[test_case(
+        TitleLanguage::English,
+        Some("Frieren"),
+        &["Frieren", "Sousou no Frieren", "葬送のフリーレン"];
+        "english then romaji"
+    )]
+    #[test_case(
+        TitleLanguage::English,
+        None,
+        &["Sousou no Frieren", "葬送のフリーレン"];
+        "missing english falls back to romaji"
+    )]
+    #[test_case(
+        TitleLanguage::English,
+        Some("Sousou no Frieren"),
+        &["Sousou no Frieren", "葬送のフリーレン"];
+        "same titles deduplicated"
+    )]
+    #[test]
+    fn test_titles_ordered(language: TitleLanguage, english: Option<&str>, expected: &[&str]) {
+        assert_eq!(titles(english).ordered(language), expected);
+    }
+
+    #[test_case(r#"{"sub":"1","exp":1760972400}"#, Some(1760972400); "integer expiry")]
+    #[test_case(r#"{"sub":"1","exp":1760972400.5}"#, Some(1760972400); "float expiry")]
+    #[test_case(r#"{"sub":"1"}"#, None; "missing expiry")]
+    #[test]
+    fn test_token_expiry(payload: &str, expected: Option<i64>) {
+        let token = format!("header.{}.signature", URL_SAFE_NO_PAD.encode(payload));
+
+        assert_eq!(token_expiry(&token).map(|d| d.timestamp()), expected);
+    }
+
+    #[test_case("not a token"; "no parts")]
+    #[test_case("header.%%%.signature"; "invalid base64")]
+    #[test]
+    fn test_token_expiry_invalid(token: &str) {
+        assert!(token_expiry(token).is_none());
+    }
+
+    #[test]
+    fn test_anilist_id_default() {
+        assert_eq!(AnilistId::default(), AnilistId(4047));
+    }
+
+    fn headers(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+
+        headers
+    }
+
+    #[test_case(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "30")], Some(30); "retry after header")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS, &[], Some(60); "missing retry after")]
+    #[test_case(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "soon")], Some(60); "invalid retry after")]
+    #[test_case(StatusCode::OK, &[("retry-after", "30")], None; "not limited")]
+    #[test]
+    fn test_retry_after(
+        status: StatusCode,
+        values: &[(&'static str, &'static str)],
+        expected: Option<u64>,
+    ) {
+        let wait = retry_after(status, &headers(values));
+        assert_eq!(wait, expected.map(Duration::from_secs));
+    }
+
+    #[test_case(&[], false; "unknown remaining")]
+    #[test_case(&[("x-ratelimit-remaining", "60")], false; "many remaining")]
+    #[test_case(&[("x-ratelimit-remaining", "2")], true; "few remaining")]
+    #[test_case(&[("x-ratelimit-remaining", "0")], true; "none remaining")]
+    #[test]
+    fn test_rate_limit_delay(values: &[(&'static str, &'static str)], throttled: bool) {
+        let rate_limit = RateLimit::default();
+        rate_limit.update(&headers(values));
+
+        assert_eq!(rate_limit.delay().is_some(), throttled);
+    }
+
+    #[test]
+    fn test_is_offline_other_errors() {
+        assert!(!is_offline(&anyhow!("unrelated error")));
+    }
+
     #[test_case(-5, 0; "negative watched clamps")]
     #[test_case(100, 100; "large watched")]
     #[test]
@@ -420,6 +2551,8 @@ mod tests {
             watched,
             id: 1,
             title: "Test".into(),
+            alternatives: vec![],
+            lists: vec![],
         };
         assert_eq!(anime.watched(), expected);
     }
@@ -438,9 +2571,311 @@ mod tests {
             watched,
             id,
             title: title.into(),
+            alternatives: vec![],
+            lists: vec![],
         };
         assert_eq!(anime.title(), title);
         assert_eq!(anime.watched(), exp_watched);
         assert_eq!(anime.id(), exp_id);
     }
+
+    fn mock_anilist(server: &MockServer) -> Anilist {
+        cache::clear().unwrap();
+
+        Anilist::default()
+            .with_endpoint(server.url())
+            .with_token("token")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_get_watching_list() {
+        let server = MockServer::start(vec![
+            ("UserQuery", mock::viewer(7, "user")),
+            (
+                "WatchingQuery",
+                mock::watching(&[(1, "Frieren", 3, 28), (2, "Dandadan", 0, 12)]),
+            ),
+        ]);
+        let anilist = mock_anilist(&server);
+
+        // sorted by title, with the episodes left to watch
+        let list = anilist.get_watching_list().await.unwrap();
+        let list: Vec<_> = list
+            .iter()
+            .map(|a| (a.id(), a.title(), a.watched()))
+            .collect();
+
+        assert_eq!(
+            list,
+            vec![(AnimeId(2), "Dandadan", 12), (AnimeId(1), "Frieren", 25)]
+        );
+        assert_eq!(
+            server.variables("WatchingQuery"),
+            vec![json!({ "id": 7, "status": ["CURRENT", "REPEATING"] })]
+        );
+        cache::clear().unwrap();
+    }
+
+    // grouped by their first list, then by title
+    #[test_case("weekly", vec![(AnimeId(3), "Kaiju No. 8"), (AnimeId(2), "Dandadan")]; "custom list")]
+    #[test_case("Planning", vec![(AnimeId(3), "Kaiju No. 8")]; "status list")]
+    #[test_case("Favourites", vec![]; "unknown list")]
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_get_named_list(name: &str, expected: Vec<(AnimeId, &str)>) {
+        let server = MockServer::start(vec![
+            ("UserQuery", mock::viewer(7, "user")),
+            (
+                "WatchingQuery",
+                mock::lists(&[
+                    ("Watching", &[(1, "Frieren", 3, 28), (2, "Dandadan", 0, 12)]),
+                    ("Planning", &[(3, "Kaiju No. 8", 0, 12)]),
+                    (
+                        "Weekly",
+                        &[(2, "Dandadan", 0, 12), (3, "Kaiju No. 8", 0, 12)],
+                    ),
+                ]),
+            ),
+        ]);
+        let anilist = mock_anilist(&server);
+
+        let list = anilist.get_named_list(name).await.unwrap();
+        let ids: Vec<_> = list.iter().map(|a| (a.id(), a.title())).collect();
+
+        assert_eq!(ids, expected);
+        // the entries of custom lists are merged with the status ones
+        if let Some(dandadan) = list.iter().find(|a| a.id() == AnimeId(2)) {
+            assert_eq!(dandadan.lists(), ["Watching", "Weekly"]);
+        }
+        // every status is requested
+        assert_eq!(server.variables("WatchingQuery"), vec![json!({ "id": 7 })]);
+        cache::clear().unwrap();
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_get_progress() {
+        let server = MockServer::start(vec![(
+            "ProgressQuery",
+            mock::progress("Frieren", 28, 5, "CURRENT"),
+        )]);
+        let anilist = mock_anilist(&server);
+
+        let progress = anilist.get_progress(AnimeId(1)).await.unwrap();
+
+        assert_eq!(progress.title(), "Frieren");
+        assert_eq!(progress.latest(), EpisodeId(5));
+        assert!(progress.has_seen(EpisodeId(5)));
+        assert!(!progress.is_last(EpisodeId(27)));
+        cache::clear().unwrap();
+    }
+
+    #[test_case(6, "CURRENT"; "next episode")]
+    #[test_case(28, "COMPLETED"; "last episode")]
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_update(episode: u32, status: &str) {
+        let server = MockServer::start(vec![
+            ("ProgressQuery", mock::progress("Frieren", 28, 5, "CURRENT")),
+            ("ProgressMutation", mock::saved()),
+        ]);
+        let mut anilist = mock_anilist(&server);
+
+        anilist
+            .update(AnimeId(1), EpisodeId(episode))
+            .await
+            .unwrap();
+
+        let saved = server.variables("ProgressMutation");
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0]["id"], 1);
+        assert_eq!(saved[0]["progress"], episode);
+        assert_eq!(saved[0]["status"], status);
+        cache::clear().unwrap();
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_update_already_seen() {
+        let server = MockServer::start(vec![
+            ("ProgressQuery", mock::progress("Frieren", 28, 5, "CURRENT")),
+            ("ProgressMutation", mock::saved()),
+        ]);
+        let mut anilist = mock_anilist(&server);
+
+        anilist.update(AnimeId(1), EpisodeId(3)).await.unwrap();
+
+        assert!(server.variables("ProgressMutation").is_empty());
+        cache::clear().unwrap();
+    }
+
+    fn journal_with(id: u32, progress: u32) {
+        let mut journal = Journal::default();
+        journal.push(Entry::new(
+            AnimeId(id),
+            EpisodeId(progress),
+            ListStatus::Current,
+            Review::default(),
+        ));
+        journal.save().unwrap();
+    }
+
+    #[test_case(Some(28); "finished anime")]
+    #[test_case(None; "airing anime")]
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_sync_journal_behind(episodes: Option<i64>) {
+        let server = MockServer::start(vec![
+            (
+                "ProgressBatchQuery",
+                mock::progress_batch(&[(1, "Frieren", 5, episodes)]),
+            ),
+            ("ProgressMutation", mock::saved()),
+        ]);
+        let anilist = mock_anilist(&server);
+        journal_with(1, 3);
+
+        // the progress reached on another device is kept
+        assert_eq!(anilist.sync_journal().await.unwrap(), 0);
+        assert!(server.variables("ProgressMutation").is_empty());
+        assert!(Journal::load().is_empty());
+        cache::clear().unwrap();
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_sync_journal_without_progress() {
+        let server = MockServer::start(vec![("ProgressMutation", mock::saved())]);
+        let anilist = mock_anilist(&server);
+        journal_with(1, 3);
+
+        assert!(anilist.sync_journal().await.is_err());
+        assert!(server.variables("ProgressMutation").is_empty());
+        assert_eq!(Journal::load().len(), 1);
+
+        Journal::default().save().unwrap();
+        cache::clear().unwrap();
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_get_franchise_with_failed_node() {
+        let server = MockServer::start(vec![
+            (
+                "RelationsQuery",
+                mock::relations(
+                    (1, "Frieren", 2023),
+                    &[(2, "Frieren 2", 2026), (3, "Frieren Movie", 2025)],
+                ),
+            ),
+            (
+                "RelationsQuery",
+                mock::relations((2, "Frieren 2", 2026), &[(4, "Frieren 3", 2028)]),
+            ),
+            ("RelationsQuery", mock::error("Too Many Requests.")),
+            (
+                "RelationsQuery",
+                mock::relations((4, "Frieren 3", 2028), &[]),
+            ),
+        ]);
+        let anilist = mock_anilist(&server);
+
+        // the movie is kept without its relations, the walk goes on
+        let franchise = anilist.get_franchise(AnimeId(1)).await.unwrap();
+        let ids: Vec<_> = franchise.iter().map(MediaInfo::id).collect();
+
+        assert_eq!(ids, vec![AnimeId(1), AnimeId(3), AnimeId(2), AnimeId(4)]);
+        cache::clear().unwrap();
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_progress_batch_error() {
+        let server = MockServer::start(vec![]);
+        let anilist = mock_anilist(&server);
+
+        assert!(
+            ProgressBatchQuery::get(&anilist, &[AnimeId(1)])
+                .await
+                .is_err()
+        );
+        assert!(anilist.get_progress_batch(&[AnimeId(1)]).await.is_empty());
+        cache::clear().unwrap();
+    }
+
+    const AIRED: i64 = 1760972400;
+    const HOUR: i64 = 60 * 60;
+
+    #[test_case(None, vec![("Frieren", 6), ("Dandadan", 3)], 1; "latest")]
+    #[test_case(Some(AIRED - 2 * HOUR), vec![("Frieren", 6)], 1; "since the last check")]
+    #[test_case(Some(AIRED), vec![], 1; "nothing new")]
+    #[test_case(
+        Some(AIRED - 5 * HOUR),
+        vec![("Frieren", 6), ("Dandadan", 3), ("Kaiju No. 8", 2)],
+        2;
+        "older page"
+    )]
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_get_notifications(
+        since: Option<i64>,
+        expected: Vec<(&str, u32)>,
+        pages: i64,
+    ) {
+        let server = MockServer::start(vec![
+            (
+                "NotificationsQuery",
+                mock::notifications(
+                    &[
+                        (1, "Frieren", 6, AIRED),
+                        (2, "Dandadan", 3, AIRED - 3 * HOUR),
+                    ],
+                    true,
+                ),
+            ),
+            (
+                "NotificationsQuery",
+                mock::notifications(
+                    &[
+                        (3, "Kaiju No. 8", 2, AIRED - 4 * HOUR),
+                        (1, "Frieren", 5, AIRED - 7 * 24 * HOUR),
+                    ],
+                    false,
+                ),
+            ),
+        ]);
+        let anilist = mock_anilist(&server);
+
+        let aired = anilist.get_notifications(since).await.unwrap();
+        let aired: Vec<_> = aired
+            .iter()
+            .map(|a| (a.media().title(), a.episode().0))
+            .collect();
+
+        assert_eq!(aired, expected);
+        // reading the notifications doesn't mark them as read
+        let variables: Vec<_> = (1..=pages)
+            .map(|page| json!({ "page": page, "reset": false }))
+            .collect();
+        assert_eq!(server.variables("NotificationsQuery"), variables);
+    }
+
+    #[tokio::test]
+    #[file_serial]
+    async fn test_mock_mark_notifications_read() {
+        let server = MockServer::start(vec![(
+            "NotificationsQuery",
+            mock::notifications(&[], false),
+        )]);
+        let anilist = mock_anilist(&server);
+
+        anilist.mark_notifications_read().await.unwrap();
+
+        assert_eq!(
+            server.variables("NotificationsQuery"),
+            vec![json!({ "page": 1, "reset": true })]
+        );
+    }
 }
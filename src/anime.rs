@@ -148,6 +148,30 @@ impl Anime {
         &self.url
     }
 
+    /// Returns the episode number of one of the urls of this anime.
+    /// Urls of single episode anime (eg. movies) are the first episode.
+    pub fn episode_of(&self, url: &str) -> EpisodeId {
+        match (self.range, get_episode_number(url)) {
+            (Some(_), Some((value, _))) => value,
+            _ => EpisodeId(1),
+        }
+    }
+
+    /// Checks if the last episode of the series is among the urls.
+    pub fn includes_last(&self, urls: &[String]) -> bool {
+        match self.range {
+            Some(r) => urls.iter().any(|u| self.episode_of(u) >= r.end),
+            None => !urls.is_empty(),
+        }
+    }
+
+    /// All the episodes of the series.
+    pub fn select_all(&self) -> Vec<String> {
+        let start = self.range.map_or(EpisodeId(1), |r| r.start);
+
+        self.select_from_index(start)
+    }
+
     pub fn select_from_index(&self, start: EpisodeId) -> Vec<String> {
         let Self { url, range, .. } = self;
 
@@ -430,6 +454,55 @@ mod tests {
         assert_eq!(anime.next_episode(), EpisodeId(expected));
     }
 
+    #[test_case("https://domain.tld/Name_Ep_07_SUB_ITA.mp4", Some((1, 12)), 7; "numbered episode")]
+    #[test_case("https://domain.tld/Name_Movie_ITA.mp4", None, 1; "movie is first episode")]
+    #[test_case("https://domain.tld/Name_Ep_07_SUB_ITA.mp4", None, 1; "single episode with number")]
+    #[test]
+    fn test_episode_of(url: &str, range: Option<(u32, u32)>, expected: u32) {
+        let range = range.map(|(s, e)| Range::new(EpisodeId(s), EpisodeId(e)));
+        let anime = Anime::new("Test", url, None, range);
+        assert_eq!(anime.episode_of(url), EpisodeId(expected));
+    }
+
+    #[test_case(&["https://domain.tld/Name_Ep_11_SUB_ITA.mp4"], Some((1, 12)), false; "last missing")]
+    #[test_case(
+        &["https://domain.tld/Name_Ep_11_SUB_ITA.mp4", "https://domain.tld/Name_Ep_12_SUB_ITA.mp4"],
+        Some((1, 12)),
+        true;
+        "last included"
+    )]
+    #[test_case(&["https://domain.tld/Name_Movie_ITA.mp4"], None, true; "movie")]
+    #[test_case(&[], None, false; "nothing selected")]
+    #[test]
+    fn test_includes_last(urls: &[&str], range: Option<(u32, u32)>, expected: bool) {
+        let range = range.map(|(s, e)| Range::new(EpisodeId(s), EpisodeId(e)));
+        let anime = Anime::new(
+            "Test",
+            "https://domain.tld/Name_Ep_01_SUB_ITA.mp4",
+            None,
+            range,
+        );
+        let urls: Vec<_> = urls.iter().map(|u| u.to_string()).collect();
+        assert_eq!(anime.includes_last(&urls), expected);
+    }
+
+    #[test_case(Some((1, 3)), 3; "whole range")]
+    #[test_case(None, 1; "single episode")]
+    #[test]
+    fn test_select_all(range: Option<(u32, u32)>, expected: usize) {
+        let range = range.map(|(s, e)| Range::new(EpisodeId(s), EpisodeId(e)));
+        let anime = Anime::new(
+            "Test",
+            "https://domain.tld/Name_Ep_01_SUB_ITA.mp4",
+            None,
+            range,
+        );
+        let episodes = anime.select_all();
+
+        assert_eq!(episodes.len(), expected);
+        assert_eq!(episodes[0], "https://domain.tld/Name_Ep_01_SUB_ITA.mp4");
+    }
+
     #[test_case(None, 0; "no range returns zero")]
     #[test_case(Some((1, 12)), 12; "range end is twelve")]
     #[test_case(Some((5, 100)), 100; "range end is one hundred")]
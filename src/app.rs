@@ -1,14 +1,61 @@
+use std::env;
+
 use anyhow::Result;
 
 use crate::{
-    cli::{Args, Command, download, stream},
-    config::clean,
+    cache,
+    cli::{
+        Args, Command, PROFILE_ENV, auth, download, list, next_season, notifications, play,
+        profile, schedule, stream, sync,
+    },
+    config,
 };
 
 pub async fn run(args: Args) -> Result<()> {
+    cache::set_refresh(args.refresh);
+    // the profiles are managed from the main configuration
+    if !matches!(args.command, Command::Profile(_)) {
+        config::set_profile(choose_profile(args.profile))?;
+    }
+
     match args.command {
         Command::Stream(cmd) => stream::exec(cmd).await,
         Command::Download(cmd) => download::exec(cmd).await,
-        Command::Clean => clean(),
+        Command::Play(cmd) => play::exec(cmd).await,
+        Command::Sync(cmd) => sync::exec(cmd).await,
+        Command::List(cmd) => list::exec(cmd).await,
+        Command::Schedule(cmd) => schedule::exec(cmd).await,
+        Command::Notifications(cmd) => notifications::exec(cmd).await,
+        Command::NextSeason(cmd) => next_season::exec(cmd).await,
+        Command::Auth(cmd) => auth::exec(cmd).await,
+        Command::Profile(cmd) => profile::exec(cmd).await,
+        Command::Clean { cache } => clean(cache),
+    }
+}
+
+/// The profile asked, or the default one. A missing profile left in the
+/// environment, or a default one deleted by hand, falls back to the main
+/// configuration.
+fn choose_profile(asked: Option<String>) -> Option<String> {
+    let profiles = config::profiles();
+    match asked {
+        Some(name)
+            if !profiles.contains(&name) && env::var(PROFILE_ENV).is_ok_and(|var| var == name) =>
+        {
+            eprintln!("Profile `{name}` of {PROFILE_ENV} not found, using the main configuration");
+            None
+        }
+        Some(name) => Some(name),
+        None => config::default_profile().filter(|p| profiles.contains(p)),
     }
 }
+
+/// Deletes the cache and, unless only the cache is asked, the configuration.
+fn clean(cache_only: bool) -> Result<()> {
+    cache::clear()?;
+    if cache_only {
+        return Ok(());
+    }
+
+    config::clean()
+}
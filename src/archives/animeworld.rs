@@ -1,29 +1,32 @@
-use anyhow::{Result, anyhow, ensure};
+use anyhow::{Result, anyhow, bail, ensure};
 use futures::stream::{self, StreamExt};
 use reqwest::{Client, Url};
 use scraper::Html;
 
 use crate::{
-    anilist::{Anilist, AnilistId},
+    anilist::AnilistId,
     anime::{Anime, AnimeId, EpisodeId},
     archives::Archive,
+    cache::{self, Kind},
     config,
     error::{RequestError, ScraperError},
     range::Range,
     scraper::{Search, selector},
+    tracker::{AnyTracker, Tracker},
     ui::Tui,
 };
 
 pub struct AnimeWorld;
 impl Archive for AnimeWorld {
+    const NAME: &'static str = "animeworld";
     const REFERRER: &'static str = "https://www.animeworld.ac";
 
     async fn get_session_id() -> Result<String> {
-        let mut session_id = match config::load("animeworld", "session_id") {
+        let mut session_id = match config::load(Self::NAME, "session_id") {
             Ok(s) if !s.is_empty() => s,
             _ => {
                 let session_id = Tui::get_session_id("AnimeWorld")?;
-                config::save("animeworld", "session_id", &session_id)?;
+                config::save(Self::NAME, "session_id", &session_id)?;
 
                 session_id
             }
@@ -41,59 +44,47 @@ impl Archive for AnimeWorld {
         client: Client,
         anilist_id: Option<AnilistId>,
     ) -> Result<Vec<Anime>> {
-        let Search { id, string } = search;
-
-        let search_results = {
-            let keyword = string;
-            let referrer = Self::REFERRER;
-
-            let search_url = format!("{referrer}/search?keyword={keyword}");
-            let search_page = selector::get_page(&client, &search_url).await?;
-
-            let anime_list = selector::from("div.film-list");
-            let Some(elem) = search_page.select(&anime_list).next() else {
-                return Err(anyhow!(RequestError::Search));
-            };
-
-            let name = selector::from("a.name");
-
-            let mut r: Vec<_> = elem
-                .select(&name)
-                .filter_map(|a| a.value().attr("href"))
-                .map(|s| s.to_string())
-                .collect();
-            r.sort_unstable();
-
-            r
-        };
-
-        ensure!(!search_results.is_empty(), "No anime found");
-
-        let anilist = Anilist::new(anilist_id)?;
-
-        let pool: Vec<_> = search_results
-            .iter()
-            .map(async |url| {
-                let url = Self::REFERRER.to_string() + url;
-                let page = selector::get_page(&client.clone(), &url).await?;
-
-                let (name, url, id, range) = get_info(page)?;
-                let anime = Anime::new(name, url, id, range);
-
-                if let Some(i) = id
-                    && let Some(p) = anilist.get_progress(i).await
-                {
-                    return Ok(anime.with_last_watched(p.latest()));
+        let Search {
+            id,
+            string,
+            alternatives,
+            exact,
+        } = search;
+
+        // the other titles are tried until the anime with the id is found
+        let mut found = None;
+        let mut error = None;
+        for keyword in std::iter::once(string.clone()).chain(alternatives) {
+            match find(&client, &keyword).await {
+                Ok(series) if id.is_some_and(|id| series.iter().any(|a| a.id() == Some(id))) => {
+                    found = Some(series);
+                    break;
                 }
+                Ok(series) if found.is_none() => found = Some(series),
+                Ok(_) => {}
+                Err(err) => error = Some(err),
+            }
+            if id.is_none() {
+                break;
+            }
+        }
+        let series = match (found, error) {
+            (Some(series), _) => series,
+            (None, Some(err)) => return Err(err),
+            (None, None) => return Err(anyhow!(RequestError::Search)),
+        };
 
-                Ok(anime)
-            })
-            .collect();
+        // a single request for the progress of all the results
+        let tracker = AnyTracker::new(anilist_id).await?;
+        let ids: Vec<_> = series.iter().filter_map(|a| a.id()).collect();
+        let progress = tracker.get_progress_batch(&ids).await;
 
-        let stream: Vec<_> = stream::iter(pool).buffer_unordered(8).collect().await;
-        let series: Vec<_> = stream
+        let series: Vec<_> = series
             .into_iter()
-            .filter_map(|a: Result<Anime>| a.ok())
+            .map(|anime| match anime.id().and_then(|i| progress.get(&i)) {
+                Some(p) => anime.with_last_watched(p.latest()),
+                None => anime,
+            })
             .collect();
 
         if let Some(id) = id
@@ -103,6 +94,9 @@ impl Archive for AnimeWorld {
         {
             return Ok(vec![a.clone()]);
         }
+        if exact {
+            bail!("{} not found in the archive", string.replace('+', " "));
+        }
 
         let mut series = series;
         if series.len() > 1 {
@@ -113,6 +107,69 @@ impl Archive for AnimeWorld {
     }
 }
 
+/// Url of the search page, with the words of the keyword (joined by `+`)
+/// encoded, as titles can contain `&`, `#` or `?`.
+fn search_url(base_url: &str, keyword: &str) -> Result<String> {
+    let mut url = Url::parse(&format!("{base_url}/search"))?;
+    url.query_pairs_mut()
+        .append_pair("keyword", &keyword.replace('+', " "));
+
+    Ok(url.into())
+}
+
+/// Searches the keyword and scrapes the page of each result.
+async fn find(client: &Client, keyword: &str) -> Result<Vec<Anime>> {
+    let base_url = AnimeWorld::base_url();
+    let search_results = {
+        let search_url = search_url(&base_url, keyword)?;
+        let search_page = selector::get_page(client, &search_url, Kind::Search).await?;
+
+        let anime_list = selector::from("div.film-list");
+        let Some(elem) = search_page.select(&anime_list).next() else {
+            // a blocked request must not be served again from the cache
+            cache::remove(Kind::Search, &search_url);
+            return Err(anyhow!(RequestError::Search));
+        };
+
+        let name = selector::from("a.name");
+
+        let mut r: Vec<_> = elem
+            .select(&name)
+            .filter_map(|a| a.value().attr("href"))
+            .map(|s| s.to_string())
+            .collect();
+        r.sort_unstable();
+
+        r
+    };
+
+    ensure!(!search_results.is_empty(), "No anime found");
+
+    let pool: Vec<_> = search_results
+        .iter()
+        .map(async |url| {
+            let url = base_url.clone() + url;
+            let page = selector::get_page(client, &url, Kind::Page).await?;
+
+            let info = get_info(page);
+            if info.is_err() {
+                cache::remove(Kind::Page, &url);
+            }
+            let (name, url, id, range) = info?;
+
+            Ok(Anime::new(name, url, id, range))
+        })
+        .collect();
+
+    let stream: Vec<_> = stream::iter(pool).buffer_unordered(8).collect().await;
+    let series = stream
+        .into_iter()
+        .filter_map(|a: Result<Anime>| a.ok())
+        .collect();
+
+    Ok(series)
+}
+
 type AnimeInfo = (String, String, Option<AnimeId>, Option<Range<EpisodeId>>);
 
 fn get_info(page: Html) -> Result<AnimeInfo> {
@@ -533,6 +590,16 @@ mod tests {
             assert_eq!(get_range(&fragment), expected);
         }
 
+        #[test_case("bunny+girl", "keyword=bunny+girl"; "words")]
+        #[test_case("Spice+&+Wolf", "keyword=Spice+%26+Wolf"; "ampersand")]
+        #[test_case("Why+Me?+#1", "keyword=Why+Me%3F+%231"; "question mark and hash")]
+        #[test]
+        fn test_search_url(keyword: &str, expected: &str) {
+            let url = search_url("https://www.animeworld.ac", keyword).unwrap();
+
+            assert_eq!(url, format!("https://www.animeworld.ac/search?{expected}"));
+        }
+
         #[tokio::test]
         #[ignore = "requires network access - use for manual testing only"]
         async fn test_remote() {
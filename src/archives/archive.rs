@@ -3,11 +3,21 @@ use std::future::Future;
 use anyhow::Result;
 use reqwest::Client;
 
-use crate::{anilist::AnilistId, anime::Anime, scraper::Search};
+use crate::{anilist::AnilistId, anime::Anime, config, scraper::Search};
 
 pub trait Archive {
+    /// Table of the archive in the configuration
+    const NAME: &'static str;
     const REFERRER: &'static str;
 
+    /// Address the archive is reached at, `base_url` in its table replaces
+    /// the default one (eg. for a mirror).
+    fn base_url() -> String {
+        config::load(Self::NAME, "base_url")
+            .map(|url| url.trim_end_matches('/').to_string())
+            .unwrap_or_else(|_| Self::REFERRER.to_string())
+    }
+
     fn get_session_id() -> impl Future<Output = Result<String>> + Send;
     fn search(
         search: Search,
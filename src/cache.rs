@@ -0,0 +1,226 @@
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+
+/// When set, the cached responses are ignored and replaced by new ones.
+static REFRESH: AtomicBool = AtomicBool::new(false);
+
+/// Kinds of cached responses, each one fresh for its own time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Display)]
+#[display(rename_all = "lowercase")]
+pub enum Kind {
+    /// The user the token belongs to
+    Viewer,
+    /// Ids of the same anime on other sites
+    Mapping,
+    /// Search results and relations of AniList
+    Media,
+    /// Pages of a series on the archives
+    Page,
+    /// Search results of the archives
+    Search,
+    /// The watching list
+    Watching,
+    /// Progress of the anime in the list
+    Progress,
+}
+
+impl Kind {
+    pub fn ttl(self) -> Duration {
+        const MINUTE: u64 = 60;
+        const HOUR: u64 = 60 * MINUTE;
+
+        let secs = match self {
+            Self::Viewer | Self::Mapping => 7 * 24 * HOUR,
+            Self::Media => 24 * HOUR,
+            Self::Page => 6 * HOUR,
+            Self::Search => HOUR,
+            Self::Watching => 5 * MINUTE,
+            Self::Progress => 30,
+        };
+
+        Duration::from_secs(secs)
+    }
+}
+
+/// A response saved on disk.
+#[derive(Debug, Serialize, Deserialize)]
+struct Cached {
+    key: String,
+    stored_at: u64,
+    value: String,
+}
+
+impl Cached {
+    fn is_fresh(&self, kind: Kind, now: u64) -> bool {
+        now.saturating_sub(self.stored_at) < kind.ttl().as_secs()
+    }
+}
+
+/// Ignores the cached responses for the rest of the run.
+pub fn set_refresh(refresh: bool) {
+    REFRESH.store(refresh, Ordering::Relaxed);
+}
+
+/// Loads a response while it is fresh.
+pub fn get(kind: Kind, key: &str) -> Option<String> {
+    if REFRESH.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let content = fs::read_to_string(path(kind, key)).ok()?;
+    let cached: Cached = serde_json::from_str(&content).ok()?;
+
+    (cached.key == key && cached.is_fresh(kind, now())).then_some(cached.value)
+}
+
+/// Saves a response, failures only mean it will be requested again.
+pub fn put(kind: Kind, key: &str, value: &str) {
+    let cached = Cached {
+        key: key.to_string(),
+        stored_at: now(),
+        value: value.to_string(),
+    };
+    let Ok(content) = serde_json::to_string(&cached) else {
+        return;
+    };
+
+    let path = path(kind, key);
+    let tmp_path = path.with_extension("tmp");
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+    }
+    if fs::write(&tmp_path, content).is_ok() {
+        fs::rename(tmp_path, path).ok();
+    }
+}
+
+/// Drops a response, eg. when it turns out to be unusable.
+pub fn remove(kind: Kind, key: &str) {
+    fs::remove_file(path(kind, key)).ok();
+}
+
+/// Drops the responses of the given kinds, eg. after the list is changed.
+pub fn invalidate(kinds: &[Kind]) {
+    let Ok(files) = fs::read_dir(config::cache_dir()) else {
+        return;
+    };
+
+    for file in files.flatten() {
+        let name = file.file_name();
+        let name = name.to_string_lossy();
+        if kinds.iter().any(|k| name.starts_with(&format!("{k}-"))) {
+            fs::remove_file(file.path()).ok();
+        }
+    }
+}
+
+/// Deletes all the cached responses.
+pub fn clear() -> Result<()> {
+    let dir = config::cache_dir();
+    if !dir.exists() {
+        return Ok(());
+    }
+
+    fs::remove_dir_all(dir).context("Unable to delete cache")
+}
+
+/// Tells apart the responses of different users of the same requests.
+pub fn scope(token: Option<&str>) -> String {
+    token
+        .map(|t| format!("{:016x}", hash(t)))
+        .unwrap_or_default()
+}
+
+fn path(kind: Kind, key: &str) -> PathBuf {
+    config::cache_dir().join(format!("{kind}-{:016x}.json", hash(key)))
+}
+
+fn hash(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::file_serial;
+    use simple_test_case::test_case;
+
+    use super::*;
+
+    #[test_case(Kind::Progress, 29, true; "progress still fresh")]
+    #[test_case(Kind::Progress, 30, false; "progress expired")]
+    #[test_case(Kind::Page, 3600, true; "page after an hour")]
+    #[test_case(Kind::Search, 3600, false; "search after an hour")]
+    #[test]
+    fn test_cached_is_fresh(kind: Kind, age: u64, expected: bool) {
+        let cached = Cached {
+            key: "key".into(),
+            stored_at: 1000,
+            value: "value".into(),
+        };
+
+        assert_eq!(cached.is_fresh(kind, 1000 + age), expected);
+    }
+
+    #[test]
+    fn test_scope() {
+        assert_eq!(scope(None), "");
+        assert_eq!(scope(Some("token")), scope(Some("token")));
+        assert_ne!(scope(Some("token")), scope(Some("other")));
+    }
+
+    #[test]
+    #[file_serial]
+    fn test_put_and_get() {
+        clear().unwrap();
+        assert_eq!(get(Kind::Media, "frieren"), None);
+
+        put(Kind::Media, "frieren", "response");
+        assert_eq!(get(Kind::Media, "frieren").as_deref(), Some("response"));
+        assert_eq!(get(Kind::Page, "frieren"), None);
+
+        put(Kind::Page, "frieren", "page");
+        remove(Kind::Page, "frieren");
+        assert_eq!(get(Kind::Page, "frieren"), None);
+
+        set_refresh(true);
+        assert_eq!(get(Kind::Media, "frieren"), None);
+        set_refresh(false);
+
+        clear().unwrap();
+        assert_eq!(get(Kind::Media, "frieren"), None);
+    }
+
+    #[test]
+    #[file_serial]
+    fn test_invalidate() {
+        clear().unwrap();
+        put(Kind::Progress, "1", "progress");
+        put(Kind::Watching, "1", "watching");
+        put(Kind::Viewer, "1", "viewer");
+
+        invalidate(&[Kind::Progress, Kind::Watching]);
+
+        assert_eq!(get(Kind::Progress, "1"), None);
+        assert_eq!(get(Kind::Watching, "1"), None);
+        assert_eq!(get(Kind::Viewer, "1").as_deref(), Some("viewer"));
+        clear().unwrap();
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::Result;
+use chrono::Local;
+use clap::{Parser, Subcommand};
+
+use crate::{
+    anilist::{Anilist, AnilistId, token_expiry},
+    config,
+};
+
+/// Manage the AniList login
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help(true))]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, global = true, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Log in with a new token
+    Login,
+    /// Delete the saved token
+    Logout,
+    /// Show the logged user and when the token expires
+    Status,
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args { action, anilist_id } = args;
+
+    match action {
+        Action::Login => {
+            config::remove("anilist", "token")?;
+            let anilist = Anilist::new(anilist_id)?;
+
+            print_status(&anilist).await
+        }
+        Action::Logout => {
+            config::remove("anilist", "token")?;
+            println!("Logged out from AniList");
+
+            Ok(())
+        }
+        Action::Status => {
+            if config::load("anilist", "token").is_err() {
+                println!("Not logged in, use `adl auth login`");
+                return Ok(());
+            }
+
+            let anilist = Anilist::new(anilist_id)?;
+
+            print_status(&anilist).await
+        }
+    }
+}
+
+async fn print_status(anilist: &Anilist) -> Result<()> {
+    // a rejected token is replaced while asking the viewer
+    let viewer = anilist.get_viewer().await?;
+    println!("Logged in as {} (id {})", viewer.name(), viewer.id());
+
+    let expiry = config::load("anilist", "token")
+        .ok()
+        .and_then(|token| token_expiry(&token));
+    match expiry {
+        Some(date) => {
+            let date = date.with_timezone(&Local).format("%d %B %Y");
+            println!("Token expires on {date}");
+        }
+        None => println!("Token expiry unknown"),
+    }
+
+    Ok(())
+}
@@ -1,6 +1,7 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use clap::Parser;
 use futures::stream::StreamExt;
 use reqwest::header::{CONTENT_LENGTH, REFERER};
@@ -9,13 +10,17 @@ use tokio::{fs, io::AsyncWriteExt};
 use tokio_stream as stream;
 
 use super::{Site, utils};
-use crate::anilist::AnilistId;
+use crate::anilist::{Anilist, AnilistId, Format};
 use crate::{
-    anime::{EpisodeId, get_episode_number},
+    anime::{Anime, AnimeId, EpisodeId, get_episode_number},
+    error::RequestError,
     range::Range,
+    scraper::Search,
     ui::Tui,
 };
 
+pub const DEFAULT_MAX_CONCURRENT: usize = 24;
+
 /// Download anime
 #[derive(Parser, Debug, Default)]
 #[command(arg_required_else_help(true))]
@@ -25,7 +30,7 @@ pub struct Args {
 
     /// Maximum number of simultaneous downloads allowed
     #[arg(
-        default_value = "24",
+        default_value_t = DEFAULT_MAX_CONCURRENT,
         short = 'm',
         long = "max-concurrent",
         name = "MAX"
@@ -33,8 +38,9 @@ pub struct Args {
     pub max_concurrent: usize,
 
     /// Root path where store files
-    #[arg(default_value = ".", short, long)]
-    pub destination: PathBuf,
+    /// [default: `destination` of the `[download]` table, or `.`]
+    #[arg(short, long)]
+    pub destination: Option<PathBuf>,
 
     /// Interactive mode
     #[arg(short, long, conflicts_with = "range")]
@@ -57,14 +63,42 @@ pub struct Args {
     #[arg(long, short = 'S', value_enum)]
     pub site: Option<Site>,
 
-    /// Retrieve anime from anilist watching list        
-    #[arg(short, long)]
-    pub watching: bool,
+    /// Retrieve anime from anilist watching list, or from the list with this
+    /// name (eg. a custom one)
+    #[arg(short, long, value_name = "LIST", num_args = 0..=1, default_missing_value = "")]
+    pub watching: Option<String>,
+
+    /// Download every season, movie and OVA of the series in one folder
+    #[arg(short, long, conflicts_with = "range")]
+    pub franchise: bool,
+
+    /// Formats downloaded with `--franchise`
+    #[arg(long, value_enum, value_delimiter = ',', default_values = ["tv", "movie", "ova", "special"])]
+    pub formats: Vec<Format>,
 }
 
-pub async fn exec(args: Args) -> Result<()> {
+/// Where the episodes are stored.
+enum Layout {
+    /// A folder for each series, in the destination
+    Series(PathBuf),
+    /// A single folder, the files are prefixed with the season they belong to
+    Franchise {
+        root: PathBuf,
+        seasons: HashMap<AnimeId, usize>,
+    },
+}
+
+pub async fn exec(mut args: Args) -> Result<()> {
+    let entries = std::mem::take(&mut args.entries);
+    let searches = utils::get_searches(entries, args.watching.as_deref(), args.anilist_id).await?;
+
+    run(args, searches).await
+}
+
+/// Downloads the anime found by the searches, then the sequels chosen for the
+/// series downloaded until the end.
+pub async fn run(args: Args, mut searches: Vec<Search>) -> Result<()> {
     let Args {
-        entries,
         max_concurrent,
         destination,
         interactive,
@@ -72,58 +106,138 @@ pub async fn exec(args: Args) -> Result<()> {
         anilist_id,
         proxy,
         site,
-        watching,
+        franchise,
+        formats,
+        ..
     } = args;
-
-    let (search_result, referrer) =
-        utils::get_search_results(entries, watching, anilist_id, proxy, site).await?;
+    let destination = utils::get_destination(destination);
 
     let ui = Tui::new();
     let client = Client::new();
 
-    // Prepare all download tasks
-    let pool = prepare_download_tasks(
-        &search_result,
-        &destination,
-        &ui,
-        interactive,
-        range,
-        client,
-        referrer,
-    )?;
+    if franchise {
+        let anilist = Anilist::anonymous()?;
+        for search in searches {
+            let Some(id) = search.id else {
+                bail!("{} not found on AniList", search.string.replace('+', " "));
+            };
+            let Some(franchise) = anilist.get_franchise(id).await else {
+                bail!(RequestError::Relations);
+            };
 
-    // Execute downloads with concurrency limit
-    stream::iter(pool)
-        .buffer_unordered(max_concurrent.max(1))
-        .collect::<Vec<_>>()
-        .await;
+            // the seasons are numbered by release date
+            let entries: Vec<_> = franchise
+                .iter()
+                .filter(|m| formats.iter().any(|f| f.matches(m)))
+                .collect();
+            let seasons = entries
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (m.id(), i + 1))
+                .collect();
+            let searches: Vec<_> = entries
+                .iter()
+                .map(|m| utils::get_from_media(m).with_exact())
+                .collect();
+
+            let (search_result, referrer) =
+                utils::search_archive(&searches, anilist_id, proxy, site).await?;
+            if search_result.is_empty() {
+                continue;
+            }
+            // named after the earliest release, whatever the anime searched
+            let Some(first) = franchise.first() else {
+                continue;
+            };
+            let layout = Layout::Franchise {
+                root: get_franchise_dir(&destination, first.title()),
+                seasons,
+            };
+
+            let (pool, _) = prepare_download_tasks(
+                &search_result,
+                &layout,
+                &ui,
+                interactive,
+                range,
+                client.clone(),
+                &referrer,
+            )?;
+
+            run_tasks(pool, max_concurrent).await;
+        }
+
+        return Ok(());
+    }
+
+    let layout = Layout::Series(destination);
+    while !searches.is_empty() {
+        let (search_result, referrer) =
+            utils::search_archive(&searches, anilist_id, proxy, site).await?;
+
+        // Prepare all download tasks
+        let (pool, completed) = prepare_download_tasks(
+            &search_result,
+            &layout,
+            &ui,
+            interactive,
+            range,
+            client.clone(),
+            &referrer,
+        )?;
+
+        run_tasks(pool, max_concurrent).await;
+
+        // the series downloaded until the end continue with the next season
+        searches = utils::get_sequels(&completed).await?;
+    }
 
     Ok(())
 }
 
+/// Execute the download tasks with a concurrency limit.
+pub async fn run_tasks<F>(pool: Vec<F>, max_concurrent: usize) -> Vec<Result<()>>
+where
+    F: std::future::Future<Output = Result<()>>,
+{
+    stream::iter(pool)
+        .buffer_unordered(max_concurrent.max(1))
+        .collect()
+        .await
+}
+
 /// Prepare all download tasks by processing search results and selecting episodes.
-fn prepare_download_tasks(
-    search_result: &[crate::anime::Anime],
-    destination: &Path,
+/// The series with their last episode selected are returned too.
+fn prepare_download_tasks<'a>(
+    search_result: &'a [Anime],
+    layout: &Layout,
     ui: &Tui,
     interactive: bool,
     range: Option<Range<EpisodeId>>,
     client: Client,
     referrer: &str,
-) -> Result<Vec<impl std::future::Future<Output = Result<()>>>> {
+) -> Result<(
+    Vec<impl std::future::Future<Output = Result<()>> + use<>>,
+    Vec<&'a Anime>,
+)> {
     let mut pool = Vec::new();
+    let mut completed = Vec::new();
     for anime in search_result {
-        let episodes: Vec<String> = match range {
-            Some(range) if !interactive => anime.select_from_range(range),
+        let episodes: Vec<String> = match (range, layout) {
+            (Some(range), _) if !interactive => anime.select_from_range(range),
+            (None, Layout::Franchise { .. }) if !interactive => anime.select_all(),
             _ => Tui::select_episodes(anime)?,
         };
+        if anime.includes_last(&episodes) {
+            completed.push(anime);
+        }
 
-        let root = {
-            let mut root = destination.to_path_buf();
-            let name = get_dir_name(anime.url())?;
-            let dir = camel_to_snake(&name);
-            root.push(dir);
-            root
+        let (root, season) = match layout {
+            Layout::Series(destination) => (get_series_dir(destination, anime.url())?, None),
+            Layout::Franchise { root, seasons } => {
+                let season = anime.id().and_then(|id| seasons.get(&id)).copied();
+                (root.clone(), season)
+            }
         };
 
         for url in episodes {
@@ -131,27 +245,126 @@ fn prepare_download_tasks(
             let client = client.clone();
             let name = anime.name().to_string();
             let referrer = referrer.to_string();
-            let dest = {
-                let mut dest = root.clone();
-                let filename = get_filename(&url)?;
-                dest.push(filename);
-                dest
-            };
-            let tmp_dest = {
-                let mut tmp_dest = dest.clone();
-                tmp_dest.add_extension("tmp");
-                tmp_dest
+            let dest = match season {
+                Some(season) => get_season_episode_path(&root, season, &url)?,
+                None => get_episode_path(&root, &url)?,
             };
 
-            let future = async move {
-                download_episode(client, &url, &referrer, &name, &dest, &tmp_dest, pb).await
-            };
-
-            pool.push(future);
+            pool.push(download_task(
+                client,
+                url,
+                referrer,
+                name,
+                dest,
+                Write::Renamed,
+                pb,
+            ));
         }
     }
 
-    Ok(pool)
+    Ok((pool, completed))
+}
+
+/// Build the directory where the episodes of an anime are stored.
+pub fn get_series_dir(destination: &Path, url: &str) -> Result<PathBuf> {
+    let mut root = destination.to_path_buf();
+    let name = get_dir_name(url)?;
+    root.push(camel_to_snake(&name));
+
+    Ok(root)
+}
+
+/// Build the directory where the episodes of a franchise are stored, from the
+/// title of its first release.
+fn get_franchise_dir(destination: &Path, title: &str) -> PathBuf {
+    let name = title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_");
+
+    destination.join(name)
+}
+
+/// Build the local path of an episode inside its series directory.
+pub fn get_episode_path(root: &Path, url: &str) -> Result<PathBuf> {
+    let mut dest = root.to_path_buf();
+    dest.push(get_filename(url)?);
+
+    Ok(dest)
+}
+
+/// Build the local path of an episode of a franchise, prefixed with its season.
+pub fn get_season_episode_path(root: &Path, season: usize, url: &str) -> Result<PathBuf> {
+    let mut dest = root.to_path_buf();
+    dest.push(format!("S{season:02}_{}", get_filename(url)?));
+
+    Ok(dest)
+}
+
+/// Find an episode downloaded with `--franchise` in the folders of the
+/// destination, whatever its season.
+pub fn find_season_episode(destination: &Path, url: &str) -> Option<PathBuf> {
+    let filename = get_filename(url).ok()?;
+
+    destination
+        .read_dir()
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_dir())
+        .filter_map(|dir| dir.read_dir().ok())
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(strip_season)
+                .is_some_and(|name| name == filename)
+        })
+}
+
+/// Removes the `S{NN}_` prefix of the episodes of a franchise.
+fn strip_season(filename: &str) -> Option<&str> {
+    let (season, name) = filename.strip_prefix('S')?.split_once('_')?;
+
+    (!season.is_empty() && season.chars().all(|c| c.is_ascii_digit())).then_some(name)
+}
+
+/// How an episode is written until it is complete.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Write {
+    /// In a `.tmp` file, renamed once complete
+    Renamed,
+    /// In the final file, so a player can follow it while it grows. An empty
+    /// `.tmp` file marks it as partial until it is complete
+    InPlace,
+}
+
+/// Build the path of the partial file written while downloading.
+pub fn get_tmp_path(dest: &Path) -> PathBuf {
+    let mut tmp_dest = dest.to_path_buf();
+    tmp_dest.add_extension("tmp");
+    tmp_dest
+}
+
+/// Checks if the episode is still being written in place, or was left
+/// incomplete.
+pub fn is_partial(dest: &Path) -> bool {
+    get_tmp_path(dest).exists()
+}
+
+/// Build the task that downloads a single episode into `dest`.
+pub async fn download_task(
+    client: Client,
+    url: String,
+    referrer: String,
+    name: String,
+    dest: PathBuf,
+    write: Write,
+    pb: indicatif::ProgressBar,
+) -> Result<()> {
+    download_episode(client, &url, &referrer, &name, &dest, write, pb).await
 }
 
 /// Download a single episode with progress tracking.
@@ -160,8 +373,8 @@ async fn download_episode(
     url: &str,
     referrer: &str,
     name: &str,
-    dest: &PathBuf,
-    tmp_dest: &PathBuf,
+    dest: &Path,
+    write: Write,
     pb: indicatif::ProgressBar,
 ) -> Result<()> {
     let source_size = get_source_size(&client, url, referrer).await?;
@@ -170,21 +383,46 @@ async fn download_episode(
     pb.set_length(source_size);
     pb.set_message(msg);
 
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let mut source = get(&client, url, referrer).await?;
+    let first = source.chunk().await?.unwrap_or_default();
+
+    // created with the first bytes, a player following the file never finds
+    // it empty
+    let tmp_dest = get_tmp_path(dest);
+    let target = match write {
+        Write::Renamed => &tmp_dest,
+        Write::InPlace => {
+            fs::File::create(&tmp_dest).await?;
+            dest
+        }
+    };
     let mut file = fs::OpenOptions::new()
         .write(true)
         .create(true)
         .truncate(true)
-        .open(tmp_dest)
+        .open(target)
         .await?;
+    file.write_all(&first).await?;
+    pb.inc(first.len() as u64);
 
-    let mut source = get(&client, url, referrer).await?;
     while let Some(chunk) = source.chunk().await? {
         file.write_all(&chunk).await?;
         pb.inc(chunk.len() as u64);
     }
 
-    fs::copy(tmp_dest, dest).await?;
-    fs::remove_file(tmp_dest).await?;
+    file.flush().await?;
+    drop(file);
+
+    // the complete file takes the place of the partial one at once, a player
+    // may still be reading it
+    match write {
+        Write::Renamed => fs::rename(&tmp_dest, dest).await?,
+        Write::InPlace => fs::remove_file(&tmp_dest).await?,
+    }
 
     pb.finish_with_message(pb.message() + " 👍");
 
@@ -349,6 +587,80 @@ mod test {
         }
     }
 
+    #[test_case(
+        "https://www.domain.tld/sub/AnimeName/AnimeName_Ep_15_SUB_ITA.mp4",
+        "/tmp/anime_name/AnimeName_Ep_15_SUB_ITA.mp4";
+        "episode path"
+    )]
+    #[test_case(
+        "https://www.domain.tld/SwordArtOnline2_Ep_01_SUB_ITA.mp4",
+        "/tmp/sword_art_online2/SwordArtOnline2_Ep_01_SUB_ITA.mp4";
+        "episode path with number"
+    )]
+    #[test]
+    fn test_get_episode_path(url: &str, expected: &str) {
+        let root = get_series_dir(Path::new("/tmp"), url).unwrap();
+        let path = get_episode_path(&root, url).unwrap();
+        assert_eq!(path, PathBuf::from(expected));
+    }
+
+    #[test_case("Sousou no Frieren", "/tmp/sousou_no_frieren"; "title with spaces")]
+    #[test_case("Re:Zero kara Hajimeru Isekai Seikatsu", "/tmp/re_zero_kara_hajimeru_isekai_seikatsu"; "title with punctuation")]
+    #[test_case("Mob Psycho 100", "/tmp/mob_psycho_100"; "title with a number")]
+    #[test]
+    fn test_get_franchise_dir(title: &str, expected: &str) {
+        let root = get_franchise_dir(Path::new("/tmp"), title);
+        assert_eq!(root, PathBuf::from(expected));
+    }
+
+    #[test_case(
+        "https://www.domain.tld/sub/AnimeName/AnimeName_Ep_15_SUB_ITA.mp4",
+        2,
+        "/tmp/anime_name/S02_AnimeName_Ep_15_SUB_ITA.mp4";
+        "episode of a season"
+    )]
+    #[test_case(
+        "https://www.domain.tld/AnimeNameMovie_SUB_ITA.mp4",
+        12,
+        "/tmp/anime_name/S12_AnimeNameMovie_SUB_ITA.mp4";
+        "movie"
+    )]
+    #[test]
+    fn test_get_season_episode_path(url: &str, season: usize, expected: &str) {
+        let root = PathBuf::from("/tmp/anime_name");
+        let path = get_season_episode_path(&root, season, url).unwrap();
+        assert_eq!(path, PathBuf::from(expected));
+    }
+
+    #[test_case("S02_AnimeName_Ep_15_SUB_ITA.mp4", Some("AnimeName_Ep_15_SUB_ITA.mp4"); "episode of a season")]
+    #[test_case("S12_AnimeNameMovie_SUB_ITA.mp4", Some("AnimeNameMovie_SUB_ITA.mp4"); "movie")]
+    #[test_case("AnimeName_Ep_15_SUB_ITA.mp4", None; "episode of a series")]
+    #[test_case("Shingeki_Ep_01_SUB_ITA.mp4", None; "title starting with s")]
+    #[test]
+    fn test_strip_season(filename: &str, expected: Option<&str>) {
+        assert_eq!(strip_season(filename), expected);
+    }
+
+    #[test]
+    fn test_find_season_episode() {
+        let destination = std::env::temp_dir().join("adl_find_season_episode");
+        let root = destination.join("anime_name");
+        std::fs::create_dir_all(&root).unwrap();
+        let url = "https://www.domain.tld/AnimeNameMovie_SUB_ITA.mp4";
+        let path = get_season_episode_path(&root, 3, url).unwrap();
+        std::fs::write(&path, b"").unwrap();
+
+        let found = find_season_episode(&destination, url);
+        let missing = find_season_episode(
+            &destination,
+            "https://www.domain.tld/AnimeName_Ep_01_SUB_ITA.mp4",
+        );
+        std::fs::remove_dir_all(&destination).unwrap();
+
+        assert_eq!(found, Some(path));
+        assert_eq!(missing, None);
+    }
+
     #[test_case("AnimeName", "anime_name"; "with simple name")]
     #[test_case("IDInvaded", "idinvaded"; "with consecutive capitals")]
     #[test_case("SwordArtOnline2", "sword_art_online2"; "with a number")]
@@ -0,0 +1,339 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use tokio::fs;
+
+use super::utils;
+use crate::{
+    anilist::{Anilist, AnilistId, EntryUpdate, ListEntry, ListStatus, Review},
+    anime::{AnimeId, EpisodeId},
+    error::RequestError,
+    export::{self, Format, Record},
+    sync::{OnComplete, SyncPolicy},
+    tracker::Tracker,
+    ui::Tui,
+};
+
+/// Manage your AniList anime list
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help(true))]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, global = true, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Show the entries of the list
+    #[command(alias = "ls")]
+    Show {
+        /// Only show the entries with this status
+        #[arg(short, long, value_enum)]
+        status: Option<ListStatus>,
+
+        /// Only show the entries whose title contains this text
+        #[arg(short, long)]
+        filter: Option<String>,
+    },
+
+    /// Change the status of an entry
+    Status {
+        /// AniList id of the series
+        id: AnimeId,
+
+        #[arg(value_enum)]
+        status: ListStatus,
+    },
+
+    /// Set the progress, the score or the notes of an entry
+    #[command(arg_required_else_help(true))]
+    Set {
+        /// AniList id of the series
+        id: AnimeId,
+
+        /// Number of watched episodes
+        #[arg(short, long)]
+        progress: Option<u32>,
+
+        /// Score in the format chosen in the AniList settings
+        #[arg(short, long)]
+        score: Option<String>,
+
+        /// Private notes about the series
+        #[arg(short, long)]
+        notes: Option<String>,
+
+        /// Date the series was finished (YYYY-MM-DD)
+        #[arg(short, long, value_name = "DATE")]
+        finished: Option<NaiveDate>,
+    },
+
+    /// Search a series and add it to the list
+    #[command(arg_required_else_help(true))]
+    Add {
+        /// Title of the series
+        query: Vec<String>,
+
+        /// Status of the new entry
+        #[arg(short, long, value_enum, default_value = "planning")]
+        status: ListStatus,
+    },
+
+    /// Remove an entry from the list
+    #[command(alias = "rm")]
+    Remove {
+        /// AniList id of the series
+        id: AnimeId,
+    },
+
+    /// Export the whole list, to move it to another tracker or as a backup
+    Export {
+        /// File to write, the list is printed when missing
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+
+        #[arg(short, long, value_enum, default_value_t)]
+        format: Format,
+    },
+
+    /// Import an exported list, showing the changes before applying them
+    #[command(arg_required_else_help(true))]
+    Import {
+        /// Exported list
+        file: PathBuf,
+
+        /// Format of the file, guessed from its extension when missing
+        #[arg(short, long, value_enum)]
+        format: Option<Format>,
+
+        /// Apply the changes without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args { action, anilist_id } = args;
+
+    let anilist = Anilist::new(anilist_id)?;
+
+    match action {
+        Action::Show { status, filter } => {
+            let Some(list) = anilist.get_list(status).await else {
+                bail!(RequestError::List);
+            };
+
+            let list = filter_entries(list, filter.as_deref());
+            if list.is_empty() {
+                println!("No entries found");
+            } else {
+                Tui::show_list(&list);
+            }
+        }
+        Action::Status { id, status } => {
+            let mut update = EntryUpdate {
+                status: Some(status),
+                ..Default::default()
+            };
+            if status == ListStatus::Completed {
+                update = update.with_review(ask_review(&anilist, id).await?);
+            }
+            anilist.set_entry(id, update).await?;
+
+            println!("Moved {id} to {status}");
+        }
+        Action::Set {
+            id,
+            progress,
+            score,
+            notes,
+            finished,
+        } => {
+            if progress.is_none() && score.is_none() && notes.is_none() && finished.is_none() {
+                bail!("nothing to set, use --progress, --score, --notes or --finished");
+            }
+
+            let score = match score {
+                Some(score) => {
+                    let format = anilist.get_viewer().await?.score_format();
+                    let Some(score) = format.parse(&score) else {
+                        bail!("invalid score `{score}`, the AniList score format is {format}");
+                    };
+                    Some(score)
+                }
+                None => None,
+            };
+
+            let progress: Option<EpisodeId> = progress.map(|p| p.into());
+            let mut update = EntryUpdate {
+                progress,
+                score,
+                notes,
+                completed_at: finished,
+                ..Default::default()
+            };
+
+            // reaching the last episode completes the series, as when streaming
+            let completes = match progress {
+                Some(number) => anilist
+                    .get_progress(id)
+                    .await
+                    .is_some_and(|p| !p.has_seen(number) && p.is_last(number)),
+                None => false,
+            };
+            if completes {
+                update.status = Some(ListStatus::Completed);
+                if update.score.is_none() {
+                    update = update.with_review(ask_review(&anilist, id).await?);
+                }
+            }
+            anilist.set_entry(id, update).await?;
+
+            println!("Updated {id}");
+        }
+        Action::Add { query, status } => {
+            let query = query.join(" ");
+            let Some(results) = anilist.search(&query).await else {
+                bail!(RequestError::MediaSearch);
+            };
+            if results.is_empty() {
+                bail!("no anime found for `{query}`");
+            }
+
+            let Some(media) = utils::select_media(&query, &results)? else {
+                bail!("no anime selected");
+            };
+            let update = EntryUpdate {
+                status: Some(status),
+                ..Default::default()
+            };
+            anilist.set_entry(media.id(), update).await?;
+
+            println!("Added {} to {status}", media.title());
+        }
+        Action::Remove { id } => {
+            anilist.remove(id).await?;
+
+            println!("Removed {id}");
+        }
+        Action::Export { output, format } => {
+            let Some(list) = anilist.get_list(None).await else {
+                bail!(RequestError::List);
+            };
+
+            let records: Vec<_> = list.iter().map(Record::from).collect();
+            let content = export::write(&records, format)?;
+            match output {
+                Some(path) => {
+                    fs::write(&path, content)
+                        .await
+                        .context("Unable to write the list")?;
+                    println!("Exported {} entries to {}", records.len(), path.display());
+                }
+                None => print!("{content}"),
+            }
+
+            let skipped = records.iter().filter(|r| r.mal_id.is_none()).count();
+            if format == Format::MalXml && skipped > 0 {
+                eprintln!("{skipped} entries without a MyAnimeList id were left out");
+            }
+        }
+        Action::Import { file, format, yes } => {
+            let Some(format) = format.or_else(|| Format::from_path(&file)) else {
+                bail!("unknown format of {}, use --format", file.display());
+            };
+            let content = fs::read_to_string(&file)
+                .await
+                .context("Unable to read the list")?;
+            let records = resolve_mal_ids(&anilist, export::read(&content, format)?).await;
+
+            let Some(list) = anilist.get_list(None).await else {
+                bail!(RequestError::List);
+            };
+            let list: Vec<_> = list.iter().map(Record::from).collect();
+
+            let changes = export::diff(&list, &records);
+            if changes.is_empty() {
+                println!("The list is already up to date");
+                return Ok(());
+            }
+            for change in &changes {
+                println!("{change}");
+            }
+            if !yes && !Tui::confirm(&format!("Apply {} changes to the list?", changes.len()))? {
+                return Ok(());
+            }
+
+            for change in &changes {
+                anilist
+                    .set_entry(change.id(), change.update().clone())
+                    .await?;
+            }
+
+            println!("Imported {} changes", changes.len());
+        }
+    }
+
+    Ok(())
+}
+
+/// Asks the review of a series being completed, when enabled in the `[sync]` table.
+async fn ask_review(anilist: &Anilist, id: AnimeId) -> Result<Review> {
+    if SyncPolicy::load()?.on_complete != OnComplete::Score {
+        return Ok(Review::default());
+    }
+
+    let title = match anilist.get_progress(id).await {
+        Some(p) if !p.title().is_empty() => p.title().to_string(),
+        _ => format!("anime {id}"),
+    };
+    let format = anilist.get_viewer().await?.score_format();
+
+    Tui::get_review(&title, format)
+}
+
+/// Fills the AniList id of the entries exported from MyAnimeList, the ones
+/// without a match are left out.
+async fn resolve_mal_ids(anilist: &Anilist, records: Vec<Record>) -> Vec<Record> {
+    let mal_ids: Vec<_> = records
+        .iter()
+        .filter(|r| r.id.is_none())
+        .filter_map(|r| r.mal_id)
+        .collect();
+    if mal_ids.is_empty() {
+        return records;
+    }
+
+    let ids = anilist.get_anilist_ids(&mal_ids).await;
+    let (records, missing): (Vec<_>, Vec<_>) = records
+        .into_iter()
+        .map(|mut r| {
+            r.id = r.id.or_else(|| ids.get(&r.mal_id?).map(|id| id.0));
+            r
+        })
+        .partition(|r| r.id.is_some());
+
+    for r in missing {
+        eprintln!("{} not found on AniList, skipped", r.title);
+    }
+
+    records
+}
+
+/// Keeps the entries whose title contains the filter, ignoring the case.
+fn filter_entries(list: Vec<ListEntry>, filter: Option<&str>) -> Vec<ListEntry> {
+    let Some(filter) = filter.map(str::to_lowercase) else {
+        return list;
+    };
+
+    list.into_iter()
+        .filter(|e| e.title().to_lowercase().contains(&filter))
+        .collect()
+}
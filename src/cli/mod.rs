@@ -1,7 +1,18 @@
 pub use clap::Parser;
 
+pub mod auth;
 pub mod download;
+pub mod list;
+pub mod next_season;
+pub mod notifications;
+pub mod play;
+pub mod profile;
+pub mod schedule;
 pub mod stream;
+pub mod sync;
+
+/// Environment variable choosing the profile, like `--profile`
+pub const PROFILE_ENV: &str = "ANIMEDL_PROFILE";
 
 #[derive(clap::ValueEnum, Debug, Clone, Copy, Default)]
 #[allow(clippy::upper_case_acronyms)]
@@ -16,6 +27,14 @@ pub enum Site {
 pub struct Args {
     #[command(subcommand)]
     pub command: Command,
+
+    /// Ignore the cached responses and request them again
+    #[arg(long, global = true)]
+    pub refresh: bool,
+
+    /// Use the tokens and settings of a profile, instead of the default ones
+    #[arg(long, global = true, env = PROFILE_ENV)]
+    pub profile: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -24,55 +43,99 @@ pub enum Command {
     Stream(stream::Args),
     #[command(alias = "d")]
     Download(download::Args),
+    #[command(alias = "p")]
+    Play(play::Args),
+    Sync(sync::Args),
+    #[command(alias = "l")]
+    List(list::Args),
+    Schedule(schedule::Args),
+    Notifications(notifications::Args),
+    NextSeason(next_season::Args),
+    Auth(auth::Args),
+    Profile(profile::Args),
 
-    /// Delete app config
-    Clean,
+    /// Delete app config and cache
+    Clean {
+        /// Delete only the cached responses
+        #[arg(long)]
+        cache: bool,
+    },
 }
 
 mod utils {
-    use anyhow::{Result, anyhow};
+    use std::path::PathBuf;
+
+    use anyhow::{Result, anyhow, bail};
     use reqwest::Client;
 
     use super::Site;
     use crate::{
-        anilist::{Anilist, AnilistId},
+        anilist::{Anilist, AnilistId, MediaInfo, WatchingAnime},
         anime::Anime,
         archives::{AnimeWorld, Archive},
+        config,
         error::RequestError,
         proxy::{ProxyConfig, get_random_proxy},
         scraper::{Scraper, ScraperConfig, Search},
+        tracker::{AnyTracker, Tracker},
         ui::Tui,
     };
 
-    async fn get_from_watching_list(client: &Anilist) -> Result<Vec<Search>> {
-        let Some(list) = client.get_watching_list().await else {
+    async fn get_from_watching_list(tracker: &impl Tracker) -> Result<Vec<Search>> {
+        let Some(list) = tracker.get_watching_list().await else {
             return Err(anyhow!(RequestError::WatchingList));
         };
 
-        let search = Tui::select_from_watching(&list)?
+        select_from_list(&list)
+    }
+
+    /// Searches from the anime in a list of AniList, by status or custom one.
+    async fn get_from_named_list(anilist: &Anilist, name: &str) -> Result<Vec<Search>> {
+        let Some(list) = anilist.get_named_list(name).await else {
+            return Err(anyhow!(RequestError::WatchingList));
+        };
+        if list.is_empty() {
+            bail!("no anime in the list `{name}`");
+        }
+
+        select_from_list(&list)
+    }
+
+    fn select_from_list(list: &[WatchingAnime]) -> Result<Vec<Search>> {
+        let search = Tui::select_from_watching(list)?
             .iter()
             .map(|info| {
-                let id = Some(info.id());
-                let string = info
-                    .title()
-                    .split_ascii_whitespace()
-                    .take(3)
-                    .enumerate()
-                    .fold(String::new(), |mut acc, (index, part)| {
-                        if index > 0 {
-                            acc.push('+');
-                        }
-                        acc.push_str(part);
-                        acc
-                    });
-
-                Search::new(string, id)
+                let mut keywords = get_keywords(info.titles());
+                let string = keywords.remove(0);
+
+                Search::new(string, Some(info.id())).with_alternatives(keywords)
             })
             .collect();
 
         Ok(search)
     }
 
+    /// Search keywords from the first words of each title, without duplicates.
+    fn get_keywords<'a>(titles: impl Iterator<Item = &'a str>) -> Vec<String> {
+        let mut keywords = Vec::new();
+        for title in titles {
+            let keyword = title
+                .split_ascii_whitespace()
+                .take(3)
+                .collect::<Vec<_>>()
+                .join("+");
+
+            if !keyword.is_empty() && !keywords.contains(&keyword) {
+                keywords.push(keyword);
+            }
+        }
+        if keywords.is_empty() {
+            keywords.push(String::new());
+        }
+
+        keywords
+    }
+
     fn get_from_input(entries: Vec<String>) -> Result<Vec<Search>> {
         let search = entries
             .join(" ")
@@ -84,11 +147,57 @@ mod utils {
         Ok(search)
     }
 
+    /// Looks up the queries on AniList, so the archive results are matched by id.
+    /// Queries without a confirmed anime keep searching by name only.
+    async fn resolve_ids(anilist: &Anilist, searches: Vec<Search>) -> Result<Vec<Search>> {
+        let mut resolved = Vec::with_capacity(searches.len());
+        for search in searches {
+            let query = search.string.replace('+', " ");
+            let results = anilist.search(&query).await.unwrap_or_default();
+            let resolved_search = match select_media(&query, &results)? {
+                Some(media) => Search::new(search.string, Some(media.id()))
+                    .with_alternatives(get_keywords(media.titles())),
+                None => search,
+            };
+
+            resolved.push(resolved_search);
+        }
+
+        Ok(resolved)
+    }
+
+    /// Asks to confirm the anime when the query is unambiguous, otherwise
+    /// to choose among the results.
+    pub fn select_media<'a>(
+        query: &str,
+        results: &'a [MediaInfo],
+    ) -> Result<Option<&'a MediaInfo>> {
+        let unambiguous = match results {
+            [] => return Ok(None),
+            [media] => Some(media),
+            _ => {
+                let mut exact = results.iter().filter(|m| m.matches(query));
+                match (exact.next(), exact.next()) {
+                    (Some(media), None) => Some(media),
+                    _ => None,
+                }
+            }
+        };
+
+        if let Some(media) = unambiguous
+            && Tui::confirm(&format!("Found {media}, is it right?"))?
+        {
+            return Ok(Some(media));
+        }
+
+        Tui::select_media(results)
+    }
+
     async fn search_site<T: Archive>(
         searches: &[Search],
         proxy: Option<String>,
         anilist_id: Option<AnilistId>,
-    ) -> Result<(Vec<Anime>, &'static str)> {
+    ) -> Result<(Vec<Anime>, String)> {
         let session_id = T::get_session_id().await?;
         let config = ScraperConfig {
             proxy,
@@ -98,24 +207,77 @@ mod utils {
 
         let anime = Scraper::new(config).search::<T>(searches).await?;
 
-        Ok((anime, T::REFERRER))
+        Ok((anime, T::base_url()))
     }
 
-    pub async fn get_search_results(
+    /// Searches from the entries, or from a list when asked or without entries.
+    pub async fn get_searches(
         entries: Vec<String>,
-        watching: bool,
+        watching: Option<&str>,
         anilist_id: Option<AnilistId>,
-        proxy: bool,
-        site: Option<Site>,
-    ) -> Result<(Vec<Anime>, &'static str)> {
-        let anilist = Anilist::new(anilist_id)?;
+    ) -> Result<Vec<Search>> {
+        match watching {
+            // the lists of other trackers are only by status
+            Some(name) if !name.is_empty() => {
+                get_from_named_list(&Anilist::new(anilist_id)?, name).await
+            }
+            Some(_) => get_from_watching_list(&AnyTracker::new(anilist_id).await?).await,
+            None if entries.is_empty() => {
+                get_from_watching_list(&AnyTracker::new(anilist_id).await?).await
+            }
+            // the search is public, no login is needed for other trackers
+            None => resolve_ids(&Anilist::anonymous()?, get_from_input(entries)?).await,
+        }
+    }
 
-        let searches = if watching || entries.is_empty() {
-            get_from_watching_list(&anilist).await?
-        } else {
-            get_from_input(entries)?
-        };
+    /// Offers the anime related to the given series, the chosen ones are
+    /// searched again on the archive.
+    pub async fn get_sequels(series: &[&Anime]) -> Result<Vec<Search>> {
+        let anilist = Anilist::anonymous()?;
 
+        let mut searches = Vec::new();
+        for anime in series {
+            let Some(id) = anime.id() else {
+                continue;
+            };
+            let related = anilist.get_relations(id).await.unwrap_or_default();
+            if related.is_empty() {
+                continue;
+            }
+            if let Some(r) = Tui::select_relation(anime.name(), &related)? {
+                searches.push(get_from_media(r.media()));
+            }
+        }
+
+        Ok(searches)
+    }
+
+    /// The destination given, or the one of the `[download]` table, or the
+    /// current folder.
+    pub fn get_destination(destination: Option<PathBuf>) -> PathBuf {
+        destination
+            .or_else(|| {
+                config::load("download", "destination")
+                    .ok()
+                    .map(PathBuf::from)
+            })
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    /// Searches an AniList anime on the archive by all its titles.
+    pub fn get_from_media(media: &MediaInfo) -> Search {
+        let mut keywords = get_keywords(media.titles());
+        let string = keywords.remove(0);
+
+        Search::new(string, Some(media.id())).with_alternatives(keywords)
+    }
+
+    pub async fn search_archive(
+        searches: &[Search],
+        anilist_id: Option<AnilistId>,
+        proxy: bool,
+        site: Option<Site>,
+    ) -> Result<(Vec<Anime>, String)> {
         let proxy = if proxy {
             let p = get_random_proxy(&Client::new(), ProxyConfig::new()).await?;
             Some(p)
@@ -124,9 +286,7 @@ mod utils {
         };
 
         let search_result = match site {
-            Some(Site::AW) | None => {
-                search_site::<AnimeWorld>(&searches, proxy, anilist_id).await?
-            }
+            Some(Site::AW) | None => search_site::<AnimeWorld>(searches, proxy, anilist_id).await?,
         };
 
         Ok(search_result)
@@ -161,6 +321,26 @@ mod utils {
             }
         }
 
+        #[test_case(
+            &["Sousou no Frieren", "Frieren: Beyond Journey's End"],
+            &["Sousou+no+Frieren", "Frieren:+Beyond+Journey's"];
+            "first three words of each title"
+        )]
+        #[test_case(
+            &["Shingeki no Kyojin", "Shingeki no Kyojin Season 2", "  "],
+            &["Shingeki+no+Kyojin"];
+            "duplicates and blank titles skipped"
+        )]
+        #[test]
+        fn test_get_keywords(titles: &[&str], expected: &[&str]) {
+            assert_eq!(get_keywords(titles.iter().copied()), expected);
+        }
+
+        #[test]
+        fn test_select_media_without_results() {
+            assert!(select_media("frieren", &[]).unwrap().is_none());
+        }
+
         #[test_case(Site::AW; "aw is default")]
         #[test]
         fn test_site_default(site: Site) {
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use clap::Parser;
+
+use super::{Site, download, stream, utils};
+use crate::{
+    anilist::{Anilist, AnilistId},
+    error::RequestError,
+    ui::Tui,
+};
+
+/// Find the sequels of an anime and stream or download them
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help(true))]
+pub struct Args {
+    /// Title of the anime already watched
+    pub query: Vec<String>,
+
+    /// Download the chosen anime instead of streaming it
+    #[arg(long)]
+    pub download: bool,
+
+    /// Root path where store files
+    /// [default: `destination` of the `[download]` table, or `.`]
+    #[arg(short, long)]
+    pub destination: Option<PathBuf>,
+
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+
+    /// Disable automatic proxy (useful for slow connections)
+    #[arg(short = 'p', long = "no-proxy", action = clap::ArgAction::SetFalse, default_value_t = true)]
+    pub proxy: bool,
+
+    /// Search anime in remote archive
+    #[arg(long, short = 'S', value_enum)]
+    pub site: Option<Site>,
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args {
+        query,
+        download,
+        destination,
+        anilist_id,
+        proxy,
+        site,
+    } = args;
+
+    let query = query.join(" ");
+    let anilist = Anilist::anonymous()?;
+    let Some(results) = anilist.search(&query).await else {
+        bail!(RequestError::MediaSearch);
+    };
+    let Some(media) = utils::select_media(&query, &results)? else {
+        return Ok(());
+    };
+
+    let Some(related) = anilist.get_relations(media.id()).await else {
+        bail!(RequestError::Relations);
+    };
+    if related.is_empty() {
+        bail!(
+            "no sequels, prequels or side stories found for {}",
+            media.title()
+        );
+    }
+    let Some(chosen) = Tui::select_relation(media.title(), &related)? else {
+        return Ok(());
+    };
+
+    let searches = vec![utils::get_from_media(chosen.media())];
+    if download {
+        let args = download::Args {
+            max_concurrent: download::DEFAULT_MAX_CONCURRENT,
+            destination,
+            interactive: true,
+            anilist_id,
+            proxy,
+            site,
+            ..Default::default()
+        };
+        download::run(args, searches).await
+    } else {
+        let args = stream::Args {
+            destination,
+            prefetch: stream::DEFAULT_PREFETCH,
+            anilist_id,
+            proxy,
+            site,
+            ..Default::default()
+        };
+        stream::run(args, searches).await
+    }
+}
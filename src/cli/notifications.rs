@@ -0,0 +1,246 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local, TimeZone};
+use clap::Parser;
+
+use super::{Site, download, utils};
+use crate::{
+    anilist::{AiredEpisode, Anilist, AnilistId, MediaInfo},
+    anime::{Anime, EpisodeId},
+    config,
+    error::RequestError,
+    range::Range,
+    ui::Tui,
+};
+
+/// Show the episodes aired since the last check and mark them as read
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Show the latest notifications, the ones already checked too
+    #[arg(long)]
+    pub all: bool,
+
+    /// Leave the notifications unread on AniList
+    #[arg(long)]
+    pub keep_unread: bool,
+
+    /// Download the aired episodes
+    #[arg(long)]
+    pub download: bool,
+
+    /// Root path where store files
+    /// [default: `destination` of the `[download]` table, or `.`]
+    #[arg(short, long)]
+    pub destination: Option<PathBuf>,
+
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+
+    /// Disable automatic proxy (useful for slow connections)
+    #[arg(short = 'p', long = "no-proxy", action = clap::ArgAction::SetFalse, default_value_t = true)]
+    pub proxy: bool,
+
+    /// Search anime in remote archive
+    #[arg(long, short = 'S', value_enum)]
+    pub site: Option<Site>,
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args {
+        all,
+        keep_unread,
+        download,
+        destination,
+        anilist_id,
+        proxy,
+        site,
+    } = args;
+
+    let anilist = Anilist::new(anilist_id)?;
+    let since = if all { None } else { last_checked() };
+    let Some(aired) = anilist.get_notifications(since).await else {
+        bail!(RequestError::Notifications);
+    };
+    if aired.is_empty() {
+        println!("No new episodes aired");
+    } else {
+        show_and_download(&aired, download, destination, anilist_id, proxy, site).await?;
+        save_last_checked(&aired)?;
+
+        // once shown, the unread count of AniList is reset
+        if !keep_unread {
+            anilist.mark_notifications_read().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Shows the aired episodes with their link on the archive, downloading them
+/// when asked.
+async fn show_and_download(
+    aired: &[AiredEpisode],
+    download: bool,
+    destination: Option<PathBuf>,
+    anilist_id: Option<AnilistId>,
+    proxy: bool,
+    site: Option<Site>,
+) -> Result<()> {
+    // the archive entry is matched by the AniList id of the notification
+    let series = group_by_media(aired);
+    let searches: Vec<_> = series
+        .iter()
+        .map(|(media, _)| utils::get_from_media(media).with_exact())
+        .collect();
+    let (search_result, _) = utils::search_archive(&searches, anilist_id, proxy, site).await?;
+
+    let rows: Vec<_> = aired
+        .iter()
+        .map(|a| {
+            let time = format_time(a.created_at(), &Local);
+            (time, a, get_link(&search_result, a))
+        })
+        .collect();
+    Tui::show_notifications(&rows);
+
+    if download {
+        for (media, range) in series {
+            let args = download::Args {
+                max_concurrent: download::DEFAULT_MAX_CONCURRENT,
+                destination: destination.clone(),
+                range: Some(range),
+                anilist_id,
+                proxy,
+                site,
+                ..Default::default()
+            };
+            download::run(args, vec![utils::get_from_media(media).with_exact()]).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Creation time of the latest notification already checked, kept for each
+/// profile.
+fn last_checked() -> Option<i64> {
+    config::load("notifications", "last_checked")
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Keeps the creation time of the latest notification checked, the next
+/// check shows only the newer ones.
+fn save_last_checked(aired: &[AiredEpisode]) -> Result<()> {
+    let Some(latest) = aired.iter().map(AiredEpisode::created_at).max() else {
+        return Ok(());
+    };
+    if last_checked().is_some_and(|t| t >= latest) {
+        return Ok(());
+    }
+
+    config::save("notifications", "last_checked", &latest.to_string())
+}
+
+/// Groups the aired episodes by anime, in the order they were notified, with
+/// the range from the first to the last episode aired.
+fn group_by_media(aired: &[AiredEpisode]) -> Vec<(&MediaInfo, Range<EpisodeId>)> {
+    let mut series: Vec<(&MediaInfo, Range<EpisodeId>)> = Vec::new();
+    for a in aired {
+        let episode = a.episode();
+        match series.iter_mut().find(|(m, _)| m.id() == a.media().id()) {
+            Some((_, range)) => {
+                *range = Range::new(episode.min(range.start), episode.max(range.end));
+            }
+            None => series.push((a.media(), Range::new(episode, episode))),
+        }
+    }
+
+    series
+}
+
+/// Url of the aired episode on the archive, none when the anime is missing.
+fn get_link(search_result: &[Anime], aired: &AiredEpisode) -> Option<String> {
+    search_result
+        .iter()
+        .find(|anime| anime.id() == Some(aired.media().id()))
+        .and_then(|anime| anime.select_from_slice(&[aired.episode()]).pop())
+}
+
+/// Day and time of a notification in the given time zone.
+fn format_time<Tz: TimeZone>(timestamp: i64, tz: &Tz) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|t| t.with_timezone(tz).format("%d %B %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anime::AnimeId;
+    use chrono::Utc;
+    use serial_test::file_serial;
+    use simple_test_case::test_case;
+
+    fn aired() -> Vec<AiredEpisode> {
+        vec![
+            AiredEpisode::new(AnimeId(1), "Frieren", 6, 1760972400),
+            AiredEpisode::new(AnimeId(2), "Dandadan", 3, 1760968800),
+            AiredEpisode::new(AnimeId(1), "Frieren", 5, 1760965200),
+        ]
+    }
+
+    #[test]
+    fn test_group_by_media() {
+        let aired = aired();
+        let series = group_by_media(&aired);
+
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0].0.title(), "Frieren");
+        assert_eq!(series[0].1, Range::new(EpisodeId(5), EpisodeId(6)));
+        assert_eq!(series[1].0.id(), AnimeId(2));
+        assert_eq!(series[1].1, Range::new(EpisodeId(3), EpisodeId(3)));
+    }
+
+    #[test_case(
+        "https://www.domain.tld/Frieren_Ep_01_SUB_ITA.mp4",
+        Some("https://www.domain.tld/Frieren_Ep_06_SUB_ITA.mp4");
+        "same anime"
+    )]
+    #[test_case("https://www.domain.tld/Dandadan_Ep_01_SUB_ITA.mp4", None; "other anime")]
+    #[test]
+    fn test_get_link(url: &str, expected: Option<&str>) {
+        let id = if url.contains("Frieren") { 1 } else { 2 };
+        let search_result = vec![Anime::new("Anime", url, Some(AnimeId(id)), None)];
+
+        assert_eq!(get_link(&search_result, &aired()[0]).as_deref(), expected);
+    }
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(1760972400, &Utc), "20 October 15:00");
+    }
+
+    #[test]
+    #[file_serial]
+    fn test_save_last_checked() {
+        config::remove("notifications", "last_checked").unwrap();
+        assert_eq!(last_checked(), None);
+
+        save_last_checked(&aired()).unwrap();
+        assert_eq!(last_checked(), Some(1760972400));
+
+        // an older check never moves it back
+        save_last_checked(&aired()[1..]).unwrap();
+        assert_eq!(last_checked(), Some(1760972400));
+
+        config::remove("notifications", "last_checked").unwrap();
+    }
+}
@@ -0,0 +1,157 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, bail};
+use clap::Parser;
+use reqwest::Url;
+
+use super::{
+    download,
+    stream::{self, TrackedEpisodes},
+};
+use crate::{
+    anilist::AnilistId,
+    anime::{Anime, AnimeId, get_episode_number},
+    range::Range,
+    tracker::{AnyTracker, Tracker},
+    ui::Tui,
+};
+
+const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "mkv", "avi", "webm"];
+
+/// Play downloaded anime from a local folder
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help(true))]
+pub struct Args {
+    /// Folder containing the downloaded episodes
+    pub dir: PathBuf,
+
+    /// AniList id of the series, required to sync the progress
+    #[arg(short, long)]
+    pub id: Option<AnimeId>,
+
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args {
+        dir,
+        id,
+        anilist_id,
+    } = args;
+
+    let files = get_local_episodes(&dir)?;
+    let Some(first) = files.first() else {
+        bail!("no episodes found in {}", dir.display())
+    };
+
+    let name = dir
+        .canonicalize()?
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let range = {
+        let first = files.iter().find_map(|f| get_episode_number(f));
+        let last = files.iter().rev().find_map(|f| get_episode_number(f));
+        first.zip(last).map(|((s, _), (e, _))| Range::new(s, e))
+    };
+
+    let mut anime = Anime::new(name, first, id, range);
+    match id {
+        Some(id) => {
+            let tracker = AnyTracker::new(anilist_id).await?;
+            if let Some(p) = tracker.get_progress(id).await {
+                anime = anime.with_last_watched(p.latest());
+            }
+        }
+        None => eprintln!("No AniList id given, progress will not be synced"),
+    }
+
+    let episodes: Vec<_> = Tui::select_episodes(&anime)?
+        .into_iter()
+        .filter(|url| files.contains(url))
+        .collect();
+
+    let ids: TrackedEpisodes = match id {
+        Some(id) => episodes
+            .iter()
+            .map(|url| (url.clone(), (id, anime.episode_of(url))))
+            .collect(),
+        None => TrackedEpisodes::new(),
+    };
+
+    stream::watch(&episodes, &ids, None, anilist_id, &mut Default::default()).await?;
+
+    Ok(())
+}
+
+/// List the video files of a folder as `file://` urls, sorted by episode. The
+/// episodes still being written, or left incomplete, are left out.
+fn get_local_episodes(dir: &Path) -> Result<Vec<String>> {
+    let mut files: Vec<_> = dir
+        .read_dir()
+        .context(format!("Unable to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_video(path))
+        .filter(|path| !download::is_partial(path))
+        .filter_map(|path| path.canonicalize().ok())
+        .filter_map(|path| Url::from_file_path(path).ok())
+        .map(String::from)
+        .collect();
+
+    files.sort_by_key(|f| (get_episode_number(f).map(|(n, _)| n), f.clone()));
+
+    Ok(files)
+}
+
+fn is_video(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| VIDEO_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case("AnimeName_Ep_01_SUB_ITA.mp4", true; "mp4 file")]
+    #[test_case("AnimeName_Ep_01_SUB_ITA.MKV", true; "uppercase extension")]
+    #[test_case("AnimeName_Ep_01_SUB_ITA.mp4.tmp", false; "partial download")]
+    #[test_case("notes.txt", false; "text file")]
+    #[test_case("AnimeName", false; "no extension")]
+    #[test]
+    fn test_is_video(filename: &str, expected: bool) {
+        assert_eq!(is_video(Path::new(filename)), expected);
+    }
+
+    #[test]
+    fn test_get_local_episodes() {
+        let dir = std::env::temp_dir().join("adl_local_episodes");
+        std::fs::create_dir_all(&dir).unwrap();
+        for f in [
+            "AnimeName_Ep_10_SUB_ITA.mp4",
+            "AnimeName_Ep_02_SUB_ITA.mp4",
+            "AnimeName_Ep_03_SUB_ITA.mp4.tmp",
+            // still being written in place
+            "AnimeName_Ep_04_SUB_ITA.mp4",
+            "AnimeName_Ep_04_SUB_ITA.mp4.tmp",
+            "cover.jpg",
+        ] {
+            std::fs::write(dir.join(f), b"").unwrap();
+        }
+
+        let files = get_local_episodes(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let episodes: Vec<_> = files
+            .iter()
+            .filter_map(|f| get_episode_number(f).map(|(n, _)| n.0))
+            .collect();
+        assert_eq!(episodes, vec![2, 10]);
+        assert!(files.iter().all(|f| f.starts_with("file://")));
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+
+use crate::config;
+
+/// Manage the profiles, each one with its own accounts and settings
+#[derive(Parser, Debug)]
+#[command(arg_required_else_help(true))]
+pub struct Args {
+    #[command(subcommand)]
+    pub action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Action {
+    /// Create a new profile, log in with `adl --profile <name> auth login`
+    #[command(arg_required_else_help(true))]
+    Add {
+        name: String,
+
+        /// Use the profile when none is chosen
+        #[arg(short, long)]
+        default: bool,
+    },
+
+    /// Show the profiles, the default one is marked
+    #[command(alias = "ls")]
+    List,
+
+    /// Delete a profile with its tokens, settings and pending updates
+    #[command(alias = "rm", arg_required_else_help(true))]
+    Remove { name: String },
+
+    /// Set the profile used when none is chosen
+    #[command(arg_required_else_help(true))]
+    Default {
+        /// Name of the profile
+        #[arg(required_unless_present = "unset")]
+        name: Option<String>,
+
+        /// Go back to the main configuration
+        #[arg(long, conflicts_with = "name")]
+        unset: bool,
+    },
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    match args.action {
+        Action::Add { name, default } => {
+            config::add_profile(&name)?;
+            if default {
+                config::set_default_profile(Some(&name))?;
+            }
+
+            println!("Created profile {name}");
+        }
+        Action::List => {
+            let profiles = config::profiles();
+            if profiles.is_empty() {
+                println!("No profiles, create one with `adl profile add <name>`");
+                return Ok(());
+            }
+
+            let default = config::default_profile();
+            for name in profiles {
+                let mark = if default.as_ref() == Some(&name) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{mark} {name}");
+            }
+        }
+        Action::Remove { name } => {
+            config::remove_profile(&name)?;
+
+            println!("Removed profile {name}");
+        }
+        Action::Default { name, .. } => match name {
+            Some(name) => {
+                config::set_profile(Some(name.clone()))?;
+                config::set_default_profile(Some(&name))?;
+
+                println!("Using profile {name} by default");
+            }
+            None => {
+                config::set_default_profile(None)?;
+
+                println!("Using the main configuration by default");
+            }
+        },
+    }
+
+    Ok(())
+}
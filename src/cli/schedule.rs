@@ -0,0 +1,240 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Local, TimeDelta, TimeZone, Utc};
+use clap::Parser;
+
+use crate::{
+    anilist::{Airing, Anilist, AnilistId},
+    error::RequestError,
+    ui::Tui,
+};
+
+const ICS_TIME: &str = "%Y%m%dT%H%M%SZ";
+const DEFAULT_DURATION: i64 = 24;
+
+/// Show when the next episodes of the watching list air
+#[derive(Parser, Debug)]
+pub struct Args {
+    /// Number of days to look ahead
+    #[arg(short = 'n', long, default_value = "7")]
+    pub days: u32,
+
+    /// Write the upcoming episodes to an iCalendar file
+    #[arg(long, value_name = "FILE")]
+    pub ics: Option<PathBuf>,
+
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+}
+
+/// Episodes airing in the same day, with their local airing time.
+type Day<'a> = (String, Vec<(String, &'a Airing)>);
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args {
+        days,
+        ics,
+        anilist_id,
+    } = args;
+
+    let anilist = Anilist::new(anilist_id)?;
+    let Some(schedule) = anilist.get_schedule().await else {
+        bail!(RequestError::Schedule);
+    };
+
+    let upcoming = get_upcoming(schedule, days);
+    if upcoming.is_empty() {
+        println!("No episodes airing in the next {days} days");
+    } else {
+        for (day, episodes) in group_by_day(&upcoming, &Local) {
+            Tui::show_schedule(&day, &episodes);
+        }
+    }
+
+    if let Some(path) = ics {
+        std::fs::write(&path, to_ics(&upcoming, Utc::now()))
+            .context(format!("Unable to write {}", path.display()))?;
+
+        println!("Calendar saved to {}", path.display());
+    }
+
+    Ok(())
+}
+
+/// Keeps the episodes airing within the given number of days.
+fn get_upcoming(schedule: Vec<Airing>, days: u32) -> Vec<Airing> {
+    let limit = i64::from(days) * 24 * 60 * 60;
+
+    schedule
+        .into_iter()
+        .filter(|a| (0..=limit).contains(&a.time_until()))
+        .collect()
+}
+
+/// Groups the episodes by the day they air in the given time zone.
+fn group_by_day<'a, Tz: TimeZone>(airings: &'a [Airing], tz: &Tz) -> Vec<Day<'a>>
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let mut days: Vec<Day> = Vec::new();
+    for airing in airings {
+        let Some(time) = DateTime::from_timestamp(airing.airing_at(), 0) else {
+            continue;
+        };
+        let time = time.with_timezone(tz);
+        let day = time.format("%A %d %B").to_string();
+        let entry = (time.format("%H:%M").to_string(), airing);
+
+        match days.last_mut() {
+            Some((last, episodes)) if *last == day => episodes.push(entry),
+            _ => days.push((day, vec![entry])),
+        }
+    }
+
+    days
+}
+
+/// Builds an iCalendar file with one event for each episode.
+fn to_ics(airings: &[Airing], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//anime-dl//schedule//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Anime schedule".to_string(),
+    ];
+
+    let stamp = now.format(ICS_TIME);
+    for airing in airings {
+        let Some(start) = DateTime::from_timestamp(airing.airing_at(), 0) else {
+            continue;
+        };
+        let duration = airing.duration().unwrap_or(DEFAULT_DURATION);
+        let end = start + TimeDelta::minutes(duration);
+        let (id, episode) = (airing.id(), airing.episode());
+
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{id}-{episode}@anime-dl"),
+            format!("DTSTAMP:{stamp}"),
+            format!("DTSTART:{}", start.format(ICS_TIME)),
+            format!("DTEND:{}", end.format(ICS_TIME)),
+            format!("SUMMARY:{} - Episode {episode}", escape(airing.title())),
+            format!("URL:https://anilist.co/anime/{id}"),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|l| fold(l) + "\r\n").collect()
+}
+
+/// Escapes the characters with a meaning in iCalendar text values.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Splits lines longer than 75 bytes, as required by the iCalendar format.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut len = 0;
+    for c in line.chars() {
+        if len + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            len = 1;
+        }
+        folded.push(c);
+        len += c.len_utf8();
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::anime::{AnimeId, EpisodeId};
+    use simple_test_case::test_case;
+
+    // Monday 20 October 2025 at 15:00 UTC
+    const MONDAY: i64 = 1760972400;
+    const DAY: i64 = 24 * 60 * 60;
+
+    fn schedule() -> Vec<Airing> {
+        vec![
+            Airing::new(1, "Frieren", 5, MONDAY, 3600),
+            Airing::new(2, "Dandadan", 3, MONDAY + 3600, 7200),
+            Airing::new(1, "Frieren", 6, MONDAY + 7 * DAY, 7 * DAY + 3600),
+        ]
+    }
+
+    #[test_case(1, 2; "one day")]
+    #[test_case(7, 2; "one week")]
+    #[test_case(8, 3; "whole schedule")]
+    #[test]
+    fn test_get_upcoming(days: u32, expected: usize) {
+        assert_eq!(get_upcoming(schedule(), days).len(), expected);
+    }
+
+    #[test]
+    fn test_group_by_day() {
+        let schedule = schedule();
+        let days = group_by_day(&schedule, &Utc);
+
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, "Monday 20 October");
+        assert_eq!(days[0].1.len(), 2);
+        assert_eq!(days[0].1[1].0, "16:00");
+        assert_eq!(days[1].0, "Monday 27 October");
+        assert_eq!(days[1].1[0].1.episode(), EpisodeId(6));
+        assert_eq!(days[1].1[0].1.id(), AnimeId(1));
+    }
+
+    #[test]
+    fn test_to_ics() {
+        let now = DateTime::from_timestamp(MONDAY - DAY, 0).unwrap();
+        let ics = to_ics(&schedule()[..1], now);
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert!(ics.contains("UID:1-5@anime-dl\r\n"));
+        assert!(ics.contains("DTSTAMP:20251019T150000Z\r\n"));
+        assert!(ics.contains("DTSTART:20251020T150000Z\r\n"));
+        assert!(ics.contains("DTEND:20251020T152400Z\r\n"));
+        assert!(ics.contains("SUMMARY:Frieren - Episode 5\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+    }
+
+    #[test_case("Frieren", "Frieren"; "plain text")]
+    #[test_case("Re:Zero, Season 3; Part 2", "Re:Zero\\, Season 3\\; Part 2"; "separators")]
+    #[test_case("a\\b\nc", "a\\\\b\\nc"; "backslash and newline")]
+    #[test]
+    fn test_escape(text: &str, expected: &str) {
+        assert_eq!(escape(text), expected);
+    }
+
+    #[test_case(10, 10; "short line")]
+    #[test_case(75, 75; "exact length")]
+    #[test_case(160, 166; "three lines")]
+    #[test]
+    fn test_fold(len: usize, expected: usize) {
+        let folded = fold(&"a".repeat(len));
+
+        assert_eq!(folded.len(), expected);
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+    }
+
+    #[test]
+    fn test_fold_multibyte() {
+        let folded = fold(&"葬".repeat(30));
+
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), "葬".repeat(30));
+    }
+}
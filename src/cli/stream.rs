@@ -1,30 +1,52 @@
 use std::collections::{HashMap, VecDeque};
-use std::process::Stdio;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use anyhow::{Context, Result, bail};
+use anyhow::{Result, bail};
 use clap::Parser;
-use reqwest::Url;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::Command;
+use futures::{FutureExt, future::BoxFuture};
+use indicatif::ProgressBar;
+use owo_colors::OwoColorize;
+use reqwest::{Client, Url};
+use tokio::task::JoinSet;
 use tokio_stream::StreamExt;
-use tokio_stream::wrappers::LinesStream;
 use which::which;
 
-use super::{Site, utils};
+use super::{Site, download, utils};
 use crate::anilist::AnilistId;
 use crate::{
-    anilist::Anilist,
-    anime::{AnimeId, EpisodeId, get_episode_number},
+    anime::{Anime, AnimeId, EpisodeId},
+    players::{Event, Mpv, Player, Vlc},
+    scraper::Search,
+    sync::{OnComplete, SyncMode, SyncPolicy},
+    tracker::{AnyTracker, Tracker},
     ui::Tui,
 };
 
+pub const DEFAULT_PREFETCH: usize = 2;
+const GROWING_POLL: Duration = Duration::from_millis(100);
+const GROWING_TIMEOUT: Duration = Duration::from_secs(10);
+
 /// Stream anime in a media player
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(arg_required_else_help(true))]
 pub struct Args {
     /// Source urls or scraper's queries
     pub entries: Vec<String>,
 
+    /// Root path where downloaded files are looked up before streaming
+    /// [default: `destination` of the `[download]` table, or `.`]
+    #[arg(short, long)]
+    pub destination: Option<PathBuf>,
+
+    /// Save streamed episodes in the destination while watching them
+    #[arg(short, long)]
+    pub save: bool,
+
+    /// Number of episodes downloaded ahead of the one playing
+    #[arg(default_value_t = DEFAULT_PREFETCH, long, requires = "save")]
+    pub prefetch: usize,
+
     /*  Common parameters */
     /// Override app id environment variable    
     #[arg(short, long, env = "ANIMEDL_ID", hide_env_values = true)]
@@ -38,131 +60,339 @@ pub struct Args {
     #[arg(long, short = 'S', value_enum)]
     pub site: Option<Site>,
 
-    /// Retrieve anime from anilist watching list        
-    #[arg(short, long)]
-    pub watching: bool,
+    /// Retrieve anime from anilist watching list, or from the list with this
+    /// name (eg. a custom one)
+    #[arg(short, long, value_name = "LIST", num_args = 0..=1, default_missing_value = "")]
+    pub watching: Option<String>,
+}
+
+pub async fn exec(mut args: Args) -> Result<()> {
+    let entries = std::mem::take(&mut args.entries);
+    let searches = utils::get_searches(entries, args.watching.as_deref(), args.anilist_id).await?;
+
+    run(args, searches).await
 }
 
-pub async fn exec(args: Args) -> Result<()> {
+/// Streams the anime found by the searches, then the sequels chosen for the
+/// series watched until the end.
+pub async fn run(args: Args, mut searches: Vec<Search>) -> Result<()> {
     let Args {
-        entries,
+        destination,
+        save,
+        prefetch,
         anilist_id,
         proxy,
         site,
-        watching,
+        ..
     } = args;
+    let destination = utils::get_destination(destination);
+
+    while !searches.is_empty() {
+        let (search_result, referrer) =
+            utils::search_archive(&searches, anilist_id, proxy, site).await?;
+
+        let completed = stream(
+            &search_result,
+            &referrer,
+            &destination,
+            save,
+            prefetch,
+            anilist_id,
+        )
+        .await?;
+
+        // the series watched until the end continue with the next season
+        let completed: Vec<_> = search_result
+            .iter()
+            .filter(|a| a.id().is_some_and(|id| completed.contains(&id)))
+            .collect();
+        searches = utils::get_sequels(&completed).await?;
+    }
 
-    let (search_result, referrer) =
-        utils::get_search_results(entries, watching, anilist_id, proxy, site).await?;
+    Ok(())
+}
 
-    let (cmd, cmd_referrer) = if let Ok(c) = which("mpv") {
-        (c, format!("--referrer={referrer}"))
-    } else if let Ok(c) = which("vlc") {
-        (c, format!("--http-referrer={referrer}"))
-    } else {
-        bail!("no supported media player found. Please install mpv or vlc.")
-    };
+/// Stream the selected episodes, saving them when asked, and return the anime
+/// completed while watching.
+async fn stream(
+    search_result: &[Anime],
+    referrer: &str,
+    destination: &Path,
+    save: bool,
+    prefetch: usize,
+    anilist_id: Option<AnilistId>,
+) -> Result<Vec<AnimeId>> {
+    // mpv can read a file while it is still being written
+    let appending = which(Mpv::NAME).is_ok();
+    let client = Client::new();
+
+    let mut tracked = Vec::new();
+    let mut episodes = Vec::new();
+    let mut growing = Vec::new();
+    let mut downloads = Prefetch::new(prefetch);
+    for anime in search_result {
+        let root = download::get_series_dir(destination, anime.url())?;
+
+        for url in Tui::select_episodes(anime)? {
+            let episode = get_local_or_remote(destination, &root, url.clone());
+
+            if save && episode == url {
+                let dest = download::get_episode_path(&root, &url)?;
+                // only the episodes downloading before the player opens them
+                // are followed while they grow, and never moved
+                let write = if appending && episodes.len() <= prefetch {
+                    growing.push((episodes.len(), dest.clone()));
+                    download::Write::InPlace
+                } else {
+                    download::Write::Renamed
+                };
 
-    let (ids, episodes) = {
-        let mut ids = HashMap::new();
-        let mut episodes = Vec::new();
-        for anime in &search_result {
-            let Some(name) = get_name_from_url(anime.url()) else {
-                continue;
-            };
+                let task = download::download_task(
+                    client.clone(),
+                    url.clone(),
+                    referrer.to_string(),
+                    anime.name().to_string(),
+                    dest,
+                    write,
+                    ProgressBar::hidden(),
+                );
+                downloads.push(episodes.len(), task);
+            }
 
-            ids.insert(name, anime.id());
-            episodes.extend(Tui::select_episodes(anime)?);
+            tracked.push(anime.id().map(|id| (id, anime.episode_of(&url))));
+            episodes.push(episode);
         }
+    }
 
-        (ids, episodes)
-    };
+    // the first episodes start downloading before the player opens them, the
+    // others once the player gets close
+    downloads.start(0);
+    for (position, dest) in growing {
+        if let Some(file) = get_growing_file(&dest).await {
+            episodes[position] = file;
+        }
+    }
 
-    let child = Command::new(cmd)
-        .arg(&cmd_referrer)
-        .arg("-v")
-        .args(&episodes)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .kill_on_drop(true)
-        .spawn()?;
-
-    let mut stream = {
-        let stdout = child
-            .stdout
-            .map(BufReader::new)
-            .context("Unable to get stdout")?;
-        let stderr = child
-            .stderr
-            .map(BufReader::new)
-            .context("Unable to get stderr")?;
-        let stdout_lines = LinesStream::new(stdout.lines());
-        let stderr_lines = LinesStream::new(stderr.lines());
-
-        StreamExt::merge(stdout_lines, stderr_lines)
-    };
+    let ids: TrackedEpisodes = episodes
+        .iter()
+        .zip(tracked)
+        .filter_map(|(episode, id)| Some((episode.clone(), id?)))
+        .collect();
+    let completed = watch(&episodes, &ids, Some(referrer), anilist_id, &mut downloads).await;
 
-    let anilist = Anilist::new(anilist_id)?;
-    let mut progress = Progress::new(anilist);
-    while let Some(Ok(line)) = stream.next().await {
-        match line {
-            line if line.contains("Opening done") => {
-                let Some(url) = line.split_whitespace().last() else {
-                    continue;
-                };
-                let Some((num, _)) = get_episode_number(url) else {
-                    continue;
-                };
-                let Some(name) = get_name_from_url(url) else {
-                    continue;
-                };
-                let Some(id) = ids.get(&name).copied().flatten() else {
-                    continue;
-                };
+    if downloads.is_running() {
+        println!("Waiting for the downloads to complete...");
+    }
+    for result in downloads.finish().await {
+        if let Err(err) = result {
+            eprintln!("{}", err.red());
+        }
+    }
 
-                progress.track(id, num);
+    completed
+}
+
+/// Downloads the episodes saved while streaming, only the one playing and the
+/// next ones, following the player along the playlist.
+#[derive(Default)]
+pub struct Prefetch {
+    /// Episodes downloaded ahead of the one playing
+    ahead: usize,
+    /// Downloads not started yet, by position of the episode in the playlist
+    pending: Vec<(usize, BoxFuture<'static, Result<()>>)>,
+    running: JoinSet<Result<()>>,
+}
+
+impl Prefetch {
+    pub fn new(ahead: usize) -> Self {
+        Self {
+            ahead,
+            ..Default::default()
+        }
+    }
+
+    fn push(&mut self, position: usize, task: impl Future<Output = Result<()>> + Send + 'static) {
+        self.pending.push((position, task.boxed()));
+    }
+
+    /// Starts the downloads of the episode at the position and of the next ones.
+    fn start(&mut self, position: usize) {
+        let range = position..=position + self.ahead;
+        let (start, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|(p, _)| range.contains(p));
+        self.pending = pending;
+
+        for (_, task) in start {
+            self.running.spawn(task);
+        }
+    }
+
+    fn is_running(&self) -> bool {
+        !self.running.is_empty()
+    }
+
+    /// Waits for the downloads started, the episodes never reached are left
+    /// out.
+    async fn finish(mut self) -> Vec<Result<()>> {
+        let mut results = Vec::new();
+        while let Some(result) = self.running.join_next().await {
+            results.push(result.unwrap_or_else(|err| Err(err.into())));
+        }
+
+        results
+    }
+}
+
+/// Maps the exact urls given to the player to the episodes they play.
+pub type TrackedEpisodes = HashMap<String, (AnimeId, EpisodeId)>;
+
+/// Play the episodes in a media player, tracking the progress on AniList.
+/// Returns the anime whose last episode was watched.
+pub async fn watch(
+    episodes: &[String],
+    ids: &TrackedEpisodes,
+    referrer: Option<&str>,
+    anilist_id: Option<AnilistId>,
+    downloads: &mut Prefetch,
+) -> Result<Vec<AnimeId>> {
+    let referrer = referrer.unwrap_or_default();
+
+    if let Ok(cmd) = which(Mpv::NAME) {
+        track::<Mpv>(&cmd, episodes, ids, referrer, anilist_id, downloads).await
+    } else if let Ok(cmd) = which(Vlc::NAME) {
+        track::<Vlc>(&cmd, episodes, ids, referrer, anilist_id, downloads).await
+    } else {
+        bail!("no supported media player found. Please install mpv or vlc.")
+    }
+}
+
+/// Follow the playback events of the player, sending the progress to AniList.
+async fn track<P: Player>(
+    cmd: &Path,
+    episodes: &[String],
+    ids: &TrackedEpisodes,
+    referrer: &str,
+    anilist_id: Option<AnilistId>,
+    downloads: &mut Prefetch,
+) -> Result<Vec<AnimeId>> {
+    let policy = SyncPolicy::load()?;
+
+    // nothing to track, no login is asked
+    if policy.mode == SyncMode::Never || ids.is_empty() {
+        let mut events = P::spawn(cmd, episodes, referrer)?;
+        while let Some(event) = events.next().await {
+            if let Event::Opened(url) = event {
+                follow(downloads, episodes, &url);
             }
+        }
+        return Ok(Vec::new());
+    }
 
-            line if line.contains('%') && !line.contains("(Paused)") => {
-                let Some(percentage) = get_percentage(&line) else {
+    let mut tracker = AnyTracker::new(anilist_id).await?;
+    offer_rewatch(&mut tracker, ids).await?;
+
+    let mut events = P::spawn(cmd, episodes, referrer)?;
+    let mut progress = Progress::new(tracker).with_policy(policy);
+    while let Some(event) = events.next().await {
+        match event {
+            Event::Opened(url) => {
+                follow(downloads, episodes, &url);
+                let Some(&(id, num)) = ids.get(&url) else {
                     continue;
                 };
 
-                progress.update(percentage);
+                progress.track(id, num);
+            }
+
+            Event::Position {
+                percentage,
+                remaining,
+            } => {
+                progress.update(percentage, remaining);
                 progress.send().await
             }
+        }
+    }
+
+    progress.flush().await?;
 
-            _ => {}
+    Ok(progress.completed().await)
+}
+
+/// Starts the downloads of the episode opened by the player and of the next ones.
+fn follow(downloads: &mut Prefetch, episodes: &[String], url: &str) {
+    if let Some(position) = episodes.iter().position(|e| e == url) {
+        downloads.start(position);
+    }
+}
+
+/// Offers to rewatch the series already watched, so their progress is tracked
+/// again from the first episode.
+async fn offer_rewatch(tracker: &mut impl Tracker, ids: &TrackedEpisodes) -> Result<()> {
+    for (id, episode) in get_first_episodes(ids) {
+        let Some(progress) = tracker.get_progress(id).await else {
+            continue;
+        };
+        if !progress.starts_rewatch(episode) {
+            continue;
+        }
+
+        let title = match progress.title() {
+            "" => format!("anime {id}"),
+            title => title.to_string(),
+        };
+        if Tui::confirm(&format!("{title} was already watched, start a rewatch?"))? {
+            tracker.rewatch(id).await?;
         }
     }
 
     Ok(())
 }
 
-// Get anime name parsing anime url.
-fn get_name_from_url(url: &str) -> Option<String> {
-    let url = Url::parse(url).ok()?;
-    let last = url.path_segments()?.next_back()?;
-    let name = last.split_terminator("_").next()?.to_string();
+/// First episode played of each anime.
+fn get_first_episodes(ids: &TrackedEpisodes) -> Vec<(AnimeId, EpisodeId)> {
+    let mut first: Vec<(AnimeId, EpisodeId)> = Vec::new();
+    for &(id, episode) in ids.values() {
+        match first.iter_mut().find(|(i, _)| *i == id) {
+            Some((_, e)) => *e = episode.min(*e),
+            None => first.push((id, episode)),
+        }
+    }
+    first.sort_unstable_by_key(|(id, _)| id.0);
 
-    Some(name)
+    first
 }
 
-/// Extract the percentage value from a player output line.
-fn get_percentage(line: &str) -> Option<u32> {
-    let sym = line.find('%')?;
-    let bytes = line.as_bytes();
+/// Wait for the first bytes of an episode downloaded in place and return it
+/// as an `appending://` url, so the player can follow it while it grows.
+/// `None` when nothing is written in time, the remote url is played instead.
+async fn get_growing_file(dest: &Path) -> Option<String> {
+    let written = async {
+        while !tokio::fs::metadata(dest).await.is_ok_and(|m| m.len() > 0) {
+            tokio::time::sleep(GROWING_POLL).await;
+        }
+    };
+    tokio::time::timeout(GROWING_TIMEOUT, written).await.ok()?;
 
-    let mut start = sym;
-    while start > 0 && bytes[start - 1].is_ascii_digit() {
-        start -= 1;
-    }
+    let path = dest.canonicalize().ok()?;
+    let url = Url::from_file_path(path).ok()?;
 
-    if start == sym {
-        return None;
-    }
+    Some(format!("appending://{}", url.path()))
+}
 
-    line.get(start..sym)?.parse().ok()
+/// Return the local copy of an episode as a `file://` url if it has
+/// already been downloaded, in its series folder or with a franchise,
+/// otherwise the remote url.
+fn get_local_or_remote(destination: &Path, root: &Path, url: String) -> String {
+    download::get_episode_path(root, &url)
+        .ok()
+        .filter(|path| path.is_file() && !download::is_partial(path))
+        .or_else(|| download::find_season_episode(destination, &url))
+        .and_then(|path| path.canonicalize().ok())
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map(String::from)
+        .unwrap_or(url)
 }
 
 #[derive(Default, Debug)]
@@ -170,22 +400,31 @@ struct EpisodeProgress {
     anime_id: AnimeId,
     episode: EpisodeId,
     percentage: u32,
+    remaining: Option<u32>,
     updated: bool,
 }
 
 #[derive(Default, Debug)]
 struct Progress {
-    anilist: Anilist,
+    tracker: AnyTracker,
+    policy: SyncPolicy,
     queue: VecDeque<EpisodeProgress>,
+    deferred: Vec<(AnimeId, EpisodeId)>,
+    /// Episodes whose progress reached the tracker
+    synced: Vec<(AnimeId, EpisodeId)>,
 }
 
 impl Progress {
-    const MIN_PERCENTAGE: u32 = 80;
-
-    pub fn new(anilist: Anilist) -> Self {
-        let queue = VecDeque::new();
+    pub fn new(tracker: AnyTracker) -> Self {
+        Self {
+            tracker,
+            ..Default::default()
+        }
+    }
 
-        Self { anilist, queue }
+    pub fn with_policy(mut self, policy: SyncPolicy) -> Self {
+        self.policy = policy;
+        self
     }
 
     pub fn track(&mut self, anime_id: AnimeId, episode: EpisodeId) {
@@ -193,19 +432,21 @@ impl Progress {
             anime_id,
             episode,
             percentage: 0,
+            remaining: None,
             updated: false,
         };
 
         self.queue.push_back(progress);
     }
 
-    pub fn update(&mut self, percentage: u32) {
+    pub fn update(&mut self, percentage: u32, remaining: Option<u32>) {
         let Self { queue, .. } = self;
 
         match queue.front_mut() {
             // update current episode
             Some(p) if p.percentage <= percentage => {
                 p.percentage = percentage;
+                p.remaining = remaining;
             }
 
             // new episode is selected, pass to the next
@@ -218,54 +459,249 @@ impl Progress {
     }
 
     pub async fn send(&mut self) {
-        let Self { anilist, queue } = self;
+        let Self {
+            tracker,
+            policy,
+            queue,
+            deferred,
+            synced,
+        } = self;
 
         if let Some(p) = queue.front_mut()
             && !p.updated
-            && p.percentage > Progress::MIN_PERCENTAGE
+            && policy.threshold.is_reached(p.percentage, p.remaining)
         {
-            let result = anilist.update(p.anime_id, p.episode).await;
-            p.updated = result.is_ok();
+            let (id, episode) = (p.anime_id, p.episode);
+
+            p.updated = match policy.mode {
+                SyncMode::Auto if policy.on_complete == OnComplete::Complete => {
+                    let sent = tracker.update(id, episode).await.is_ok();
+                    if sent {
+                        synced.push((id, episode));
+                    }
+                    sent
+                }
+
+                // the final episode needs a score, which is asked once the player is closed
+                SyncMode::Auto => match tracker.get_progress(id).await {
+                    Some(progress) if progress.is_last(episode) => {
+                        deferred.push((id, episode));
+                        true
+                    }
+                    _ => {
+                        let sent = tracker.update(id, episode).await.is_ok();
+                        if sent {
+                            synced.push((id, episode));
+                        }
+                        sent
+                    }
+                },
+
+                SyncMode::Ask => {
+                    deferred.push((id, episode));
+                    true
+                }
+
+                SyncMode::Never => true,
+            };
         }
     }
+
+    /// Sends the updates that need user input, once the player is closed.
+    pub async fn flush(&mut self) -> Result<()> {
+        let Self {
+            tracker,
+            policy,
+            deferred,
+            synced,
+            ..
+        } = self;
+
+        for (id, episode) in get_latest_episodes(deferred) {
+            let progress = tracker.get_progress(id).await;
+            if progress.as_ref().is_some_and(|p| p.has_seen(episode)) {
+                synced.push((id, episode));
+                continue;
+            }
+
+            let title = match &progress {
+                Some(p) if !p.title().is_empty() => p.title().to_string(),
+                _ => format!("anime {id}"),
+            };
+
+            if policy.mode == SyncMode::Ask
+                && !Tui::confirm(&format!("Sync episode {episode} of {title}?"))?
+            {
+                continue;
+            }
+
+            match progress {
+                Some(p) if p.is_last(episode) && policy.on_complete == OnComplete::Score => {
+                    let format = tracker.score_format().await;
+                    let review = Tui::get_review(&title, format)?;
+                    tracker.complete(id, episode, review).await?
+                }
+                _ => tracker.update(id, episode).await?,
+            }
+            synced.push((id, episode));
+        }
+
+        deferred.clear();
+
+        Ok(())
+    }
+
+    /// Anime whose last episode was watched and synced in this session.
+    pub async fn completed(&self) -> Vec<AnimeId> {
+        let mut completed = Vec::new();
+        for (id, episode) in get_latest_episodes(&self.synced) {
+            if let Some(progress) = self.tracker.get_progress(id).await
+                && progress.is_last(episode)
+            {
+                completed.push(id);
+            }
+        }
+
+        completed
+    }
+}
+
+/// Keeps only the latest watched episode of each anime, in watching order.
+fn get_latest_episodes(episodes: &[(AnimeId, EpisodeId)]) -> Vec<(AnimeId, EpisodeId)> {
+    let mut latest: Vec<(AnimeId, EpisodeId)> = Vec::new();
+    for &(id, episode) in episodes {
+        match latest.iter_mut().find(|(i, _)| *i == id) {
+            Some((_, e)) => *e = episode.max(*e),
+            None => latest.push((id, episode)),
+        }
+    }
+
+    latest
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::anilist::Anilist;
+    use crate::anime::get_episode_number;
     use simple_test_case::test_case;
-
-    #[test_case("[status] 9%", Some(9); "single digit")]
-    #[test_case("[status] 100%", Some(100); "triple digit")]
-    #[test_case("[status] 09%", Some(9); "leading zero")]
-    #[test_case("[status] %", None; "missing digits")]
-    #[test_case("[status] no percent", None; "missing percent")]
-    #[test_case("50%", Some(50); "at start of line")]
-    #[test_case("AV: 00.00% (Paused)", Some(0); "paused parses digits before percent")]
-    #[test_case("  75%", Some(75); "with leading spaces")]
-    #[test_case("1000%", Some(1000); "four digits")]
-    #[test]
-    fn test_get_percentage(input: &str, expected: Option<u32>) {
-        assert_eq!(get_percentage(input), expected);
-    }
+    use std::sync::{Arc, Mutex};
 
     #[test_case(
-        "https://www.domain.tld/sub/anotherSub/AnimeName/AnimeName_Ep_0017_SUB_ITA.mp4",
-        Some("AnimeName");
-        "standard url"
+        "https://www.domain.tld/AnimeName_Ep_01_SUB_ITA.mp4",
+        false;
+        "remote when missing"
     )]
     #[test_case(
-        "https://www.domain.tld/AnimeName_Ep_01.mp4",
-        Some("AnimeName");
-        "short url"
+        "https://www.domain.tld/AnimeName_Ep_02_SUB_ITA.mp4",
+        true;
+        "local when downloaded"
     )]
-    #[test_case("not a url", None; "invalid url")]
-    #[test_case("ftp://", None; "url without path segments")]
-    #[test_case("https://www.domain.tld/", None; "root path no segments")]
-    #[test_case("https://www.domain.tld/_no_name", Some(""); "underscore prefix")]
     #[test]
-    fn test_get_name_from_url(url: &str, expected: Option<&str>) {
-        assert_eq!(get_name_from_url(url).as_deref(), expected);
+    fn test_get_local_or_remote(url: &str, local: bool) {
+        let root = std::env::temp_dir().join("adl_local_or_remote");
+        let path = download::get_episode_path(&root, url).unwrap();
+        if local {
+            std::fs::create_dir_all(&root).unwrap();
+            std::fs::write(&path, b"").unwrap();
+        }
+
+        let result = get_local_or_remote(&root, &root, url.to_string());
+        if local {
+            std::fs::remove_file(&path).unwrap();
+            assert!(result.starts_with("file://"));
+            assert!(result.ends_with(url.rsplit('/').next().unwrap()));
+        } else {
+            assert_eq!(result, url);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_growing_file() {
+        let dir = std::env::temp_dir().join("adl_growing_file");
+        let dest = dir.join("AnimeName_Ep_03_SUB_ITA.mp4");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // the player gets the final file once written, it is not moved once
+        // complete
+        std::fs::write(download::get_tmp_path(&dest), b"").unwrap();
+        std::fs::write(&dest, b"data").unwrap();
+        let url = get_growing_file(&dest).await.unwrap();
+        assert_eq!(
+            get_local_or_remote(
+                &dir,
+                &dir,
+                "https://domain.tld/AnimeName_Ep_03_SUB_ITA.mp4".into()
+            ),
+            "https://domain.tld/AnimeName_Ep_03_SUB_ITA.mp4"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(url.starts_with("appending:///"));
+        assert!(url.ends_with("AnimeName_Ep_03_SUB_ITA.mp4"));
+        assert_eq!(get_episode_number(&url), Some((EpisodeId(3), 2)));
+    }
+
+    fn prefetch(ahead: usize, episodes: usize, started: &Arc<Mutex<Vec<usize>>>) -> Prefetch {
+        let mut downloads = Prefetch::new(ahead);
+        for position in 0..episodes {
+            let started = Arc::clone(started);
+            let task = async move {
+                started.lock().unwrap().push(position);
+                Ok(())
+            };
+            downloads.push(position, task);
+        }
+
+        downloads
+    }
+
+    #[test_case(1, 0, vec![0, 1]; "first episode")]
+    #[test_case(2, 3, vec![3, 4]; "end of the playlist")]
+    #[test_case(0, 2, vec![2]; "nothing ahead")]
+    #[tokio::test]
+    async fn test_prefetch_start(ahead: usize, position: usize, expected: Vec<usize>) {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let mut downloads = prefetch(ahead, 5, &started);
+
+        downloads.start(position);
+        let results = downloads.finish().await;
+
+        let mut started = started.lock().unwrap().clone();
+        started.sort_unstable();
+        assert_eq!(started, expected);
+        assert_eq!(results.len(), expected.len());
+    }
+
+    #[tokio::test]
+    async fn test_prefetch_follows_player() {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let mut downloads = prefetch(1, 5, &started);
+        let episodes: Vec<_> = (0..5).map(|i| format!("ep{i}")).collect();
+
+        // skipping ahead starts the episode opened, the skipped one is left out
+        downloads.start(0);
+        follow(&mut downloads, &episodes, "ep3");
+        follow(&mut downloads, &episodes, "ep4");
+        downloads.finish().await;
+
+        let mut started = started.lock().unwrap().clone();
+        started.sort_unstable();
+        assert_eq!(started, vec![0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn test_get_first_episodes() {
+        let ids = TrackedEpisodes::from([
+            ("a".to_string(), (AnimeId(2), EpisodeId(4))),
+            ("b".to_string(), (AnimeId(1), EpisodeId(7))),
+            ("c".to_string(), (AnimeId(2), EpisodeId(3))),
+        ]);
+
+        assert_eq!(
+            get_first_episodes(&ids),
+            vec![(AnimeId(1), EpisodeId(7)), (AnimeId(2), EpisodeId(3))]
+        );
     }
 
     #[test_case(AnimeId(1), EpisodeId(5); "track single")]
@@ -274,7 +710,7 @@ mod tests {
     fn test_progress_track(anime_id: AnimeId, episode: EpisodeId) {
         let anilist = Anilist::new(None);
         let Ok(anilist) = anilist else { return };
-        let mut progress = Progress::new(anilist);
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist));
 
         assert!(progress.queue.is_empty());
 
@@ -294,7 +730,7 @@ mod tests {
     fn test_progress_track_multiple(count: usize) {
         let anilist = Anilist::new(None);
         let Ok(anilist) = anilist else { return };
-        let mut progress = Progress::new(anilist);
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist));
 
         for i in 0..count {
             progress.track(AnimeId(i as u32), EpisodeId(i as u32));
@@ -311,12 +747,12 @@ mod tests {
     fn test_progress_update(initial: u32, new: u32, expected: u32) {
         let anilist = Anilist::new(None);
         let Ok(anilist) = anilist else { return };
-        let mut progress = Progress::new(anilist);
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist));
 
         progress.track(AnimeId(1), EpisodeId(1));
         progress.queue.front_mut().unwrap().percentage = initial;
 
-        progress.update(new);
+        progress.update(new, None);
 
         assert_eq!(progress.queue.front().unwrap().percentage, expected);
     }
@@ -326,13 +762,13 @@ mod tests {
     fn test_progress_update_pop(updated: bool, percentage: u32, expected_empty: bool) {
         let anilist = Anilist::new(None);
         let Ok(anilist) = anilist else { return };
-        let mut progress = Progress::new(anilist);
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist));
 
         progress.track(AnimeId(1), EpisodeId(1));
         progress.queue.front_mut().unwrap().updated = updated;
         progress.queue.front_mut().unwrap().percentage = percentage;
 
-        progress.update(0);
+        progress.update(0, None);
 
         assert_eq!(progress.queue.is_empty(), expected_empty);
     }
@@ -343,9 +779,29 @@ mod tests {
     fn test_progress_update_no_queue(percentage: u32) {
         let anilist = Anilist::new(None);
         let Ok(anilist) = anilist else { return };
-        let mut progress = Progress::new(anilist);
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist));
+
+        progress.update(percentage, None);
+    }
+
+    #[tokio::test]
+    async fn test_progress_completed_not_synced() {
+        let anilist = Anilist::new(None);
+        let Ok(anilist) = anilist else { return };
+        let policy = SyncPolicy {
+            mode: SyncMode::Ask,
+            ..Default::default()
+        };
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist)).with_policy(policy);
 
-        progress.update(percentage);
+        // waiting for the confirmation, the episode is not synced yet
+        progress.track(AnimeId(1), EpisodeId(12));
+        progress.update(100, None);
+        progress.send().await;
+
+        assert_eq!(progress.deferred, vec![(AnimeId(1), EpisodeId(12))]);
+        assert!(progress.synced.is_empty());
+        assert!(progress.completed().await.is_empty());
     }
 
     #[test_case(30, 50, 40, 50; "higher then lower")]
@@ -354,13 +810,13 @@ mod tests {
     fn test_progress_update_sequence(init: u32, first: u32, second: u32, expected: u32) {
         let anilist = Anilist::new(None);
         let Ok(anilist) = anilist else { return };
-        let mut progress = Progress::new(anilist);
+        let mut progress = Progress::new(AnyTracker::Anilist(anilist));
 
         progress.track(AnimeId(1), EpisodeId(1));
         progress.queue.front_mut().unwrap().percentage = init;
 
-        progress.update(first);
-        progress.update(second);
+        progress.update(first, None);
+        progress.update(second, None);
 
         assert_eq!(progress.queue.front().unwrap().percentage, expected);
     }
@@ -379,6 +835,7 @@ mod tests {
             anime_id,
             episode,
             percentage,
+            remaining: None,
             updated,
         };
         assert_eq!(p.anime_id, anime_id);
@@ -387,6 +844,41 @@ mod tests {
         assert_eq!(p.updated, updated);
     }
 
+    #[test_case(
+        vec![(1, 3), (2, 1), (1, 4), (1, 2)],
+        vec![(1, 4), (2, 1)];
+        "latest episode per anime"
+    )]
+    #[test_case(vec![], vec![]; "nothing watched")]
+    #[test]
+    fn test_get_latest_episodes(episodes: Vec<(u32, u32)>, expected: Vec<(u32, u32)>) {
+        let episodes: Vec<_> = episodes
+            .into_iter()
+            .map(|(a, e)| (AnimeId(a), EpisodeId(e)))
+            .collect();
+        let expected: Vec<_> = expected
+            .into_iter()
+            .map(|(a, e)| (AnimeId(a), EpisodeId(e)))
+            .collect();
+        assert_eq!(get_latest_episodes(&episodes), expected);
+    }
+
+    #[test_case(50, Some(600), Some(600); "remaining stored")]
+    #[test_case(50, None, None; "remaining unknown")]
+    #[test]
+    fn test_progress_update_remaining(
+        percentage: u32,
+        remaining: Option<u32>,
+        expected: Option<u32>,
+    ) {
+        let mut progress = Progress::default();
+
+        progress.track(AnimeId(1), EpisodeId(1));
+        progress.update(percentage, remaining);
+
+        assert_eq!(progress.queue.front().unwrap().remaining, expected);
+    }
+
     #[test_case(0, true; "default is empty")]
     #[test]
     fn test_progress_default(_dummy: u32, expected_empty: bool) {
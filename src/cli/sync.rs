@@ -0,0 +1,33 @@
+use anyhow::Result;
+use clap::Parser;
+
+use crate::{
+    anilist::{Anilist, AnilistId},
+    journal::Journal,
+};
+
+/// Send the progress updates saved while offline
+#[derive(Parser, Debug)]
+pub struct Args {
+    /*  Common parameters */
+    /// Override app id environment variable
+    #[arg(short, long, env = "ANIMEDL_ID", hide_env_values = true)]
+    pub anilist_id: Option<AnilistId>,
+}
+
+pub async fn exec(args: Args) -> Result<()> {
+    let Args { anilist_id } = args;
+
+    if Journal::load().is_empty() {
+        println!("Nothing to sync");
+        return Ok(());
+    }
+
+    let anilist = Anilist::new(anilist_id)?;
+    let synced = anilist.sync_journal().await?;
+    let pending = Journal::load().len();
+
+    println!("Synced {synced} updates, {pending} still pending");
+
+    Ok(())
+}
@@ -1,9 +1,13 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail, ensure};
 use toml_edit::Document;
 
+/// The profile in use, its configuration is kept apart from the main one.
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
 /// Loads a value from the configuration
 pub fn load(table: &str, key: &str) -> Result<String> {
     let toml = load_toml().context("Unable to read configuration file")?;
@@ -23,9 +27,11 @@ pub fn load(table: &str, key: &str) -> Result<String> {
 
 /// Saves a value in the configuration
 pub fn save(table: &str, key: &str, value: &str) -> Result<()> {
-    let path = config_path();
+    save_at(&config_path(), table, key, value)
+}
 
-    let toml = match load_toml() {
+fn save_at(path: &Path, table: &str, key: &str, value: &str) -> Result<()> {
+    let toml = match load_toml_at(path) {
         Ok(t) => t,
         Err(_) => {
             if let Some(p) = path.parent() {
@@ -47,14 +53,151 @@ pub fn save(table: &str, key: &str, value: &str) -> Result<()> {
         doc.to_string()
     };
 
-    safe_save(&config, &path)?;
+    safe_save(&config, path)?;
+
+    Ok(())
+}
+
+/// Removes a value from the configuration, a missing value is not an error
+pub fn remove(table: &str, key: &str) -> Result<()> {
+    remove_at(&config_path(), table, key)
+}
+
+fn remove_at(path: &Path, table: &str, key: &str) -> Result<()> {
+    let Ok(toml) = load_toml_at(path) else {
+        return Ok(());
+    };
+
+    let config = {
+        let mut doc = toml.into_mut();
+        if let Some(t) = doc.get_mut(table).and_then(|t| t.as_table_like_mut()) {
+            t.remove(key);
+        }
+
+        doc.to_string()
+    };
+
+    safe_save(&config, path)
+}
+
+/// Loads a file stored alongside the configuration
+pub fn load_file(name: &str) -> Result<String> {
+    let path = file_path(name);
+
+    fs::read_to_string(&path).context(format!("Unable to read {}", path.display()))
+}
+
+/// Saves a file alongside the configuration
+pub fn save_file(name: &str, content: &str) -> Result<()> {
+    let path = file_path(name);
+    if let Some(p) = path.parent() {
+        fs::create_dir_all(p)?;
+    }
+
+    safe_save(content, &path)
+}
+
+/// Uses the configuration of a profile for the rest of the run, the main one
+/// when `None`.
+pub fn set_profile(name: Option<String>) -> Result<()> {
+    if let Some(name) = &name {
+        ensure!(
+            profiles().contains(name),
+            "profile `{name}` not found, create it with `adl profile add {name}`"
+        );
+    }
+
+    *PROFILE.write().unwrap_or_else(|e| e.into_inner()) = name;
 
     Ok(())
 }
 
+/// The profile in use, `None` for the main configuration.
+pub fn profile() -> Option<String> {
+    PROFILE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Names of the saved profiles, sorted.
+pub fn profiles() -> Vec<String> {
+    let Ok(dirs) = fs::read_dir(profiles_dir()) else {
+        return vec![];
+    };
+
+    let mut names: Vec<_> = dirs
+        .flatten()
+        .filter(|d| d.path().is_dir())
+        .filter_map(|d| d.file_name().into_string().ok())
+        .collect();
+    names.sort();
+
+    names
+}
+
+/// Creates an empty profile, with its own tokens, session ids and settings.
+pub fn add_profile(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    ensure!(
+        valid,
+        "a profile name can only have letters, digits, `-` and `_`"
+    );
+    if profiles().iter().any(|p| p == name) {
+        bail!("profile `{name}` already exists");
+    }
+
+    fs::create_dir_all(profiles_dir().join(name)).context("Unable to create profile")
+}
+
+/// Deletes a profile with all its data, it stops being the default one.
+pub fn remove_profile(name: &str) -> Result<()> {
+    ensure!(
+        profiles().iter().any(|p| p == name),
+        "profile `{name}` not found"
+    );
+
+    if default_profile().as_deref() == Some(name) {
+        set_default_profile(None)?;
+    }
+
+    fs::remove_dir_all(profiles_dir().join(name)).context("Unable to delete profile")
+}
+
+/// The profile used when none is chosen, from the main configuration.
+pub fn default_profile() -> Option<String> {
+    let toml = load_toml_at(&main_config_path()).ok()?;
+    let name = toml.get("profile")?.get("default")?.as_str()?;
+
+    Some(name.to_string())
+}
+
+/// Sets the profile used when none is chosen, the main configuration when `None`.
+pub fn set_default_profile(name: Option<&str>) -> Result<()> {
+    let path = main_config_path();
+
+    match name {
+        Some(name) => save_at(&path, "profile", "default", name),
+        None => remove_at(&path, "profile", "default"),
+    }
+}
+
+/// Returns the directory of the cached responses, alongside the configuration
+pub fn cache_dir() -> PathBuf {
+    config_path().with_file_name("cache")
+}
+
+/// Returns the path of a file stored alongside the configuration
+fn file_path(name: &str) -> PathBuf {
+    config_path().with_file_name(name)
+}
+
 /// Loads and parses the TOML configuration file
 fn load_toml() -> Result<Document<String>> {
-    let path = config_path();
+    load_toml_at(&config_path())
+}
+
+fn load_toml_at(path: &Path) -> Result<Document<String>> {
     let content = fs::read_to_string(path)?;
     let toml = content.parse::<Document<String>>()?;
 
@@ -89,8 +232,25 @@ const CONFIG_PATH: &str = ".config/anime-dl/config.toml";
 #[cfg(windows)]
 const CONFIG_PATH: &str = r"AppData\Roaming\anime-dl\config.toml";
 
-/// Returns the configuration file path for the current OS
+/// Returns the configuration file path of the profile in use
 fn config_path() -> PathBuf {
+    let path = main_config_path();
+
+    match profile() {
+        Some(name) => profiles_dir()
+            .join(name)
+            .join(path.file_name().unwrap_or_default()),
+        None => path,
+    }
+}
+
+/// Returns the directory the profiles are saved in, alongside the configuration
+fn profiles_dir() -> PathBuf {
+    main_config_path().with_file_name("profiles")
+}
+
+/// Returns the main configuration file path for the current OS
+fn main_config_path() -> PathBuf {
     let root = {
         #[cfg(test)]
         {
@@ -195,6 +355,22 @@ mod tests {
         clean().unwrap();
     }
 
+    #[test]
+    #[file_serial]
+    fn test_remove() {
+        clean().ok();
+        assert!(remove(TEST_TABLE, "test").is_ok());
+
+        save(TEST_TABLE, "test", TEST_DATA).unwrap();
+        save(TEST_TABLE, "other", TEST_DATA).unwrap();
+        remove(TEST_TABLE, "test").unwrap();
+        remove("missing", "test").unwrap();
+
+        assert!(load(TEST_TABLE, "test").is_err());
+        assert_eq!(load(TEST_TABLE, "other").unwrap(), TEST_DATA);
+        clean().unwrap();
+    }
+
     #[test_case("config.toml"; "ends with config.toml")]
     #[test]
     fn test_config_path(expected_suffix: &str) {
@@ -202,6 +378,25 @@ mod tests {
         assert!(path.to_string_lossy().ends_with(expected_suffix));
     }
 
+    #[test_case("journal.json"; "json file")]
+    #[test_case("cache.toml"; "toml file")]
+    #[test]
+    fn test_file_path(name: &str) {
+        let path = file_path(name);
+        assert!(path.ends_with(name));
+        assert_eq!(path.parent(), config_path().parent());
+    }
+
+    #[test_case("test_file.txt", TEST_DATA; "save and load file")]
+    #[test]
+    #[file_serial]
+    fn test_save_and_load_file(name: &str, content: &str) {
+        save_file(name, content).unwrap();
+        assert_eq!(load_file(name).unwrap(), content);
+        fs::remove_file(file_path(name)).unwrap();
+        assert!(load_file(name).is_err());
+    }
+
     #[test_case(TEST_DATA; "save creates dir")]
     #[test]
     #[file_serial]
@@ -220,4 +415,47 @@ mod tests {
         clean().unwrap();
         assert!(load(TEST_TABLE, "test").is_err());
     }
+
+    #[test]
+    #[file_serial]
+    fn test_profiles() {
+        fs::remove_dir_all(profiles_dir()).ok();
+        clean().ok();
+        save("anilist", "token", "main").unwrap();
+
+        add_profile("alice").unwrap();
+        add_profile("bob").unwrap();
+        assert!(add_profile("alice").is_err());
+        assert_eq!(profiles(), ["alice", "bob"]);
+
+        // each profile has its own values
+        set_profile(Some("alice".into())).unwrap();
+        assert!(load("anilist", "token").is_err());
+        save("anilist", "token", "alice").unwrap();
+        assert_eq!(load("anilist", "token").unwrap(), "alice");
+        assert!(file_path("journal.json").starts_with(profiles_dir().join("alice")));
+
+        set_profile(None).unwrap();
+        assert_eq!(load("anilist", "token").unwrap(), "main");
+
+        set_default_profile(Some("bob")).unwrap();
+        assert_eq!(default_profile().as_deref(), Some("bob"));
+        remove_profile("bob").unwrap();
+        assert_eq!(default_profile(), None);
+        assert_eq!(profiles(), ["alice"]);
+
+        assert!(set_profile(Some("bob".into())).is_err());
+        assert!(remove_profile("bob").is_err());
+
+        fs::remove_dir_all(profiles_dir()).unwrap();
+        clean().unwrap();
+    }
+
+    #[test_case(""; "empty")]
+    #[test_case("a/b"; "path")]
+    #[test_case("with space"; "space")]
+    #[test]
+    fn test_add_profile_invalid(name: &str) {
+        assert!(add_profile(name).is_err());
+    }
 }
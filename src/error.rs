@@ -14,6 +14,20 @@ pub enum RangeError {
 pub enum RequestError {
     #[error("unable to get data from watching list")]
     WatchingList,
+    #[error("unable to get data from anime list")]
+    List,
+    #[error("unable to search anime on AniList")]
+    MediaSearch,
+    #[error("unable to get the airing schedule")]
+    Schedule,
+    #[error("unable to get the notifications")]
+    Notifications,
+    #[error("unable to get the related anime")]
+    Relations,
+    #[error("the AniList token was rejected, log in again with `adl auth login`")]
+    Unauthorized,
+    #[error("AniList rate limit exceeded, try again in a minute")]
+    RateLimit,
     #[error("blocked search request")]
     Search,
     #[error("session ID is required to access this archive")]
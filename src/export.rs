@@ -0,0 +1,662 @@
+use std::fmt;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    anilist::{EntryUpdate, ListEntry, ListStatus},
+    anime::AnimeId,
+};
+
+/// Formats the list is exported to and imported from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// The export of MyAnimeList, read by most trackers
+    #[default]
+    MalXml,
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// Guesses the format from the extension of a file.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "xml" => Some(Self::MalXml),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// An entry of an exported list.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    /// AniList id, missing in the MyAnimeList exports
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mal_id: Option<u32>,
+    pub title: String,
+    #[serde(default)]
+    pub episodes: Option<u32>,
+    pub status: ListStatus,
+    #[serde(default)]
+    pub progress: u32,
+    /// Out of 10, none when unrated
+    #[serde(default)]
+    pub score: Option<f64>,
+    #[serde(default)]
+    pub repeat: u32,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub started_at: Option<NaiveDate>,
+    #[serde(default)]
+    pub completed_at: Option<NaiveDate>,
+}
+
+impl From<&ListEntry> for Record {
+    fn from(entry: &ListEntry) -> Self {
+        Self {
+            id: Some(entry.id().0),
+            mal_id: entry.mal_id(),
+            title: entry.title().to_string(),
+            episodes: entry.episodes(),
+            status: entry.status(),
+            progress: entry.latest().into(),
+            score: entry.rating(),
+            repeat: entry.repeat(),
+            notes: entry.notes().map(str::to_string),
+            started_at: entry.started_at(),
+            completed_at: entry.completed_at(),
+        }
+    }
+}
+
+/// Writes the entries in the format, MyAnimeList skips the ones without
+/// a MyAnimeList id.
+pub fn write(records: &[Record], format: Format) -> Result<String> {
+    match format {
+        Format::MalXml => Ok(write_mal_xml(records)),
+        Format::Json => serde_json::to_string_pretty(records).context("Unable to write the list"),
+        Format::Csv => Ok(write_csv(records)),
+    }
+}
+
+pub fn read(content: &str, format: Format) -> Result<Vec<Record>> {
+    match format {
+        Format::MalXml => read_mal_xml(content),
+        Format::Json => serde_json::from_str(content).context("Invalid JSON list"),
+        Format::Csv => read_csv(content),
+    }
+}
+
+/// Changes an import makes to an entry of the list.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    id: AnimeId,
+    title: String,
+    /// Missing from the list until now
+    added: bool,
+    /// Name of the changed fields, with their old and new value
+    fields: Vec<(&'static str, String, String)>,
+    update: EntryUpdate,
+}
+
+impl Change {
+    pub fn id(&self) -> AnimeId {
+        self.id
+    }
+
+    /// The changed fields only, the others are left untouched.
+    pub fn update(&self) -> &EntryUpdate {
+        &self.update
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.added { '+' } else { '~' };
+        let fields: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(name, old, new)| match self.added {
+                true => format!("{name} {new}"),
+                false => format!("{name} {old} → {new}"),
+            })
+            .collect();
+
+        write!(f, "{sign} {}: {}", self.title, fields.join(", "))
+    }
+}
+
+/// Changes needed to bring the list in line with the imported entries, which
+/// must have an AniList id. Entries missing from the import are kept, and so
+/// are the fields it leaves empty.
+pub fn diff(list: &[Record], imported: &[Record]) -> Vec<Change> {
+    imported
+        .iter()
+        .filter_map(|record| {
+            let id = record.id?;
+            let current = list.iter().find(|r| r.id == Some(id));
+
+            let mut update = EntryUpdate::default();
+            let mut fields = Vec::new();
+            let mut compare = |name, old: Option<String>, new: String| {
+                if old.as_ref() != Some(&new) {
+                    fields.push((name, old.unwrap_or_default(), new));
+                    true
+                } else {
+                    false
+                }
+            };
+
+            if compare(
+                "status",
+                current.map(|c| c.status.to_string()),
+                record.status.to_string(),
+            ) {
+                update.status = Some(record.status);
+            }
+            if compare(
+                "progress",
+                current.map(|c| c.progress.to_string()),
+                record.progress.to_string(),
+            ) {
+                update.progress = Some(record.progress.into());
+            }
+            if let Some(score) = record.score
+                && compare(
+                    "score",
+                    current.map(|c| format_score(c.score)),
+                    format_score(Some(score)),
+                )
+            {
+                update.score_raw = Some((score * 10.0).round() as i64);
+            }
+            if compare(
+                "rewatched",
+                current.map(|c| c.repeat.to_string()),
+                record.repeat.to_string(),
+            ) {
+                update.repeat = Some(record.repeat.into());
+            }
+            if let Some(notes) = &record.notes
+                && compare(
+                    "notes",
+                    current.and_then(|c| c.notes.clone()),
+                    notes.clone(),
+                )
+            {
+                update.notes = Some(notes.clone());
+            }
+            if let Some(date) = record.started_at
+                && compare(
+                    "started",
+                    current.and_then(|c| c.started_at).map(|d| d.to_string()),
+                    date.to_string(),
+                )
+            {
+                update.started_at = Some(date);
+            }
+            if let Some(date) = record.completed_at
+                && compare(
+                    "finished",
+                    current.and_then(|c| c.completed_at).map(|d| d.to_string()),
+                    date.to_string(),
+                )
+            {
+                update.completed_at = Some(date);
+            }
+
+            (!fields.is_empty()).then(|| Change {
+                id: AnimeId(id),
+                title: record.title.clone(),
+                added: current.is_none(),
+                fields,
+                update,
+            })
+        })
+        .collect()
+}
+
+fn format_score(score: Option<f64>) -> String {
+    match score {
+        Some(score) => format!("{score:.1}"),
+        None => "-".into(),
+    }
+}
+
+fn write_mal_xml(records: &[Record]) -> String {
+    let records: Vec<_> = records.iter().filter(|r| r.mal_id.is_some()).collect();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" ?>\n<myanimelist>\n");
+    xml += "  <myinfo>\n";
+    xml += "    <user_export_type>1</user_export_type>\n";
+    xml += &format!(
+        "    <user_total_anime>{}</user_total_anime>\n",
+        records.len()
+    );
+    xml += "  </myinfo>\n";
+
+    let date = |date: Option<NaiveDate>| match date {
+        Some(date) => date.to_string(),
+        None => "0000-00-00".into(),
+    };
+    for r in records {
+        let status = match r.status {
+            ListStatus::Current | ListStatus::Repeating => "Watching",
+            ListStatus::Completed => "Completed",
+            ListStatus::Paused => "On-Hold",
+            ListStatus::Dropped => "Dropped",
+            ListStatus::Planning => "Plan to Watch",
+        };
+        let fields = [
+            (
+                "series_animedb_id",
+                r.mal_id.unwrap_or_default().to_string(),
+            ),
+            ("series_title", cdata(&r.title)),
+            (
+                "series_episodes",
+                r.episodes.unwrap_or_default().to_string(),
+            ),
+            ("my_watched_episodes", r.progress.to_string()),
+            ("my_start_date", date(r.started_at)),
+            ("my_finish_date", date(r.completed_at)),
+            ("my_score", r.score.unwrap_or_default().round().to_string()),
+            ("my_status", status.to_string()),
+            ("my_comments", cdata(r.notes.as_deref().unwrap_or_default())),
+            ("my_times_watched", r.repeat.to_string()),
+            (
+                "my_rewatching",
+                u8::from(r.status == ListStatus::Repeating).to_string(),
+            ),
+            ("update_on_import", "1".into()),
+        ];
+
+        xml += "  <anime>\n";
+        for (tag, value) in fields {
+            xml += &format!("    <{tag}>{value}</{tag}>\n");
+        }
+        xml += "  </anime>\n";
+    }
+    xml += "</myanimelist>\n";
+
+    xml
+}
+
+fn cdata(text: &str) -> String {
+    format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+fn read_mal_xml(content: &str) -> Result<Vec<Record>> {
+    if !content.contains("<myanimelist>") {
+        bail!("Invalid MyAnimeList export");
+    }
+
+    content
+        .split("<anime>")
+        .skip(1)
+        .map(|anime| {
+            let anime = anime.split("</anime>").next().unwrap_or_default();
+            let field = |tag: &str| xml_text(anime, tag).unwrap_or_default();
+            let number = |tag: &str| field(tag).trim().parse::<u32>().ok();
+            let date = |tag: &str| NaiveDate::parse_from_str(field(tag).trim(), "%Y-%m-%d").ok();
+
+            let mal_id = number("series_animedb_id").filter(|&id| id > 0);
+            let status = match field("my_status").trim() {
+                _ if number("my_rewatching") == Some(1) => ListStatus::Repeating,
+                "Watching" | "1" => ListStatus::Current,
+                "Completed" | "2" => ListStatus::Completed,
+                "On-Hold" | "3" => ListStatus::Paused,
+                "Dropped" | "4" => ListStatus::Dropped,
+                "Plan to Watch" | "6" => ListStatus::Planning,
+                other => bail!("Unknown MyAnimeList status `{other}`"),
+            };
+
+            Ok(Record {
+                id: None,
+                mal_id: Some(mal_id.ok_or(anyhow!("MyAnimeList entry without an id"))?),
+                title: field("series_title"),
+                episodes: number("series_episodes").filter(|&e| e > 0),
+                status,
+                progress: number("my_watched_episodes").unwrap_or_default(),
+                score: number("my_score").filter(|&s| s > 0).map(f64::from),
+                repeat: number("my_times_watched").unwrap_or_default(),
+                notes: Some(field("my_comments")).filter(|n| !n.is_empty()),
+                started_at: date("my_start_date"),
+                completed_at: date("my_finish_date"),
+            })
+        })
+        .collect()
+}
+
+/// Text of the first element with the tag, without CDATA and entities.
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let start = xml.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let end = start + xml[start..].find(&format!("</{tag}>"))?;
+    let text = xml[start..end].trim();
+
+    let text = match text.strip_prefix("<![CDATA[") {
+        Some(data) => data.strip_suffix("]]>")?.replace("]]]]><![CDATA[>", "]]>"),
+        None => text
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    };
+
+    Some(text)
+}
+
+const CSV_HEADER: [&str; 11] = [
+    "id",
+    "mal_id",
+    "title",
+    "episodes",
+    "status",
+    "progress",
+    "score",
+    "repeat",
+    "started_at",
+    "completed_at",
+    "notes",
+];
+
+fn write_csv(records: &[Record]) -> String {
+    let mut csv = CSV_HEADER.join(",") + "\n";
+
+    let optional = |value: Option<String>| value.unwrap_or_default();
+    for r in records {
+        let status = r
+            .status
+            .to_possible_value()
+            .map(|v| v.get_name().to_string());
+        let row = [
+            optional(r.id.map(|id| id.to_string())),
+            optional(r.mal_id.map(|id| id.to_string())),
+            csv_field(&r.title),
+            optional(r.episodes.map(|e| e.to_string())),
+            optional(status),
+            r.progress.to_string(),
+            optional(r.score.map(|s| s.to_string())),
+            r.repeat.to_string(),
+            optional(r.started_at.map(|d| d.to_string())),
+            optional(r.completed_at.map(|d| d.to_string())),
+            csv_field(r.notes.as_deref().unwrap_or_default()),
+        ];
+
+        csv += &row.join(",");
+        csv += "\n";
+    }
+
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn read_csv(content: &str) -> Result<Vec<Record>> {
+    let mut rows = csv_rows(content).into_iter();
+    let header = rows.next().context("Empty CSV list")?;
+    let column = |name: &str| header.iter().position(|h| h.trim() == name);
+
+    let columns: Vec<_> = CSV_HEADER.iter().map(|name| column(name)).collect();
+    let (Some(_), Some(_)) = (columns[0], columns[4]) else {
+        bail!("The CSV list needs the `id` and `status` columns");
+    };
+
+    rows.filter(|row| row.iter().any(|v| !v.trim().is_empty()))
+        .map(|row| {
+            let field = |i: usize| {
+                columns[i]
+                    .and_then(|c| row.get(c))
+                    .map(|v| v.trim())
+                    .filter(|v| !v.is_empty())
+            };
+            let number = |i: usize| field(i).and_then(|v| v.parse::<u32>().ok());
+            let date =
+                |i: usize| field(i).and_then(|v| NaiveDate::parse_from_str(v, "%Y-%m-%d").ok());
+
+            let status = field(4).unwrap_or_default();
+            let status = <ListStatus as ValueEnum>::from_str(status, true)
+                .map_err(|_| anyhow!("Unknown status `{status}`"))?;
+
+            Ok(Record {
+                id: number(0),
+                mal_id: number(1),
+                title: field(2).unwrap_or_default().to_string(),
+                episodes: number(3),
+                status,
+                progress: number(5).unwrap_or_default(),
+                score: field(6).and_then(|v| v.parse().ok()),
+                repeat: number(7).unwrap_or_default(),
+                started_at: date(8),
+                completed_at: date(9),
+                notes: field(10).map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Splits the CSV in rows of fields, quoted fields can hold commas, quotes
+/// and new lines.
+fn csv_rows(content: &str) -> Vec<Vec<String>> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => row.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use simple_test_case::test_case;
+
+    use super::*;
+    use crate::anime::EpisodeId;
+
+    fn record() -> Record {
+        Record {
+            id: Some(154587),
+            mal_id: Some(52991),
+            title: "Sousou no Frieren, \"Beyond\" ]]> Journey's End".into(),
+            episodes: Some(28),
+            status: ListStatus::Completed,
+            progress: 28,
+            score: Some(9.0),
+            repeat: 1,
+            notes: Some("Great ending,\nwith <tags> & more".into()),
+            started_at: NaiveDate::from_ymd_opt(2023, 9, 29),
+            completed_at: NaiveDate::from_ymd_opt(2024, 3, 22),
+        }
+    }
+
+    #[test_case(Format::Json; "json")]
+    #[test_case(Format::Csv; "csv")]
+    #[test]
+    fn test_write_read(format: Format) {
+        let records = vec![
+            record(),
+            Record {
+                id: Some(1),
+                mal_id: None,
+                title: "Planned".into(),
+                episodes: None,
+                status: ListStatus::Planning,
+                progress: 0,
+                score: None,
+                repeat: 0,
+                notes: None,
+                started_at: None,
+                completed_at: None,
+            },
+        ];
+
+        let content = write(&records, format).unwrap();
+        assert_eq!(read(&content, format).unwrap(), records);
+    }
+
+    #[test]
+    fn test_write_read_mal_xml() {
+        let mut planned = record();
+        planned.mal_id = None;
+
+        let content = write(&[record(), planned], Format::MalXml).unwrap();
+        assert!(content.contains("<user_total_anime>1</user_total_anime>"));
+
+        // the AniList id is not part of the MyAnimeList export
+        let expected = Record {
+            id: None,
+            ..record()
+        };
+        assert_eq!(read(&content, Format::MalXml).unwrap(), vec![expected]);
+    }
+
+    #[test]
+    fn test_read_mal_xml() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8" ?>
+            <myanimelist>
+                <anime>
+                    <series_animedb_id>37450</series_animedb_id>
+                    <series_title><![CDATA[Seishun Buta Yarou wa Bunny Girl Senpai no Yume wo Minai]]></series_title>
+                    <series_episodes>13</series_episodes>
+                    <my_watched_episodes>5</my_watched_episodes>
+                    <my_start_date>0000-00-00</my_start_date>
+                    <my_finish_date>0000-00-00</my_finish_date>
+                    <my_score>0</my_score>
+                    <my_status>On-Hold</my_status>
+                    <my_comments><![CDATA[]]></my_comments>
+                    <my_times_watched>0</my_times_watched>
+                    <my_rewatching>0</my_rewatching>
+                </anime>
+            </myanimelist>"#;
+
+        let records = read(xml, Format::MalXml).unwrap();
+        assert_eq!(
+            records,
+            vec![Record {
+                id: None,
+                mal_id: Some(37450),
+                title: "Seishun Buta Yarou wa Bunny Girl Senpai no Yume wo Minai".into(),
+                episodes: Some(13),
+                status: ListStatus::Paused,
+                progress: 5,
+                score: None,
+                repeat: 0,
+                notes: None,
+                started_at: None,
+                completed_at: None,
+            }]
+        );
+    }
+
+    #[test_case("<list></list>"; "not a mal export")]
+    #[test_case("<myanimelist><anime><series_animedb_id>1</series_animedb_id><my_status>Watched</my_status></anime></myanimelist>"; "unknown status")]
+    #[test]
+    fn test_read_mal_xml_err(xml: &str) {
+        assert!(read(xml, Format::MalXml).is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let list = vec![record()];
+        let imported = vec![
+            Record {
+                status: ListStatus::Repeating,
+                progress: 3,
+                notes: None,
+                ..record()
+            },
+            Record {
+                id: Some(1),
+                title: "New".into(),
+                status: ListStatus::Planning,
+                progress: 0,
+                score: None,
+                repeat: 0,
+                notes: None,
+                started_at: None,
+                completed_at: None,
+                ..record()
+            },
+            Record {
+                id: None,
+                ..record()
+            },
+        ];
+
+        let changes = diff(&list, &imported);
+        assert_eq!(changes.len(), 2);
+
+        assert_eq!(
+            changes[0].to_string(),
+            "~ Sousou no Frieren, \"Beyond\" ]]> Journey's End: status Completed → Rewatching, progress 28 → 3"
+        );
+        assert_eq!(
+            changes[0].update(),
+            &EntryUpdate {
+                status: Some(ListStatus::Repeating),
+                progress: Some(EpisodeId(3)),
+                ..Default::default()
+            }
+        );
+
+        assert_eq!(changes[1].id(), AnimeId(1));
+        assert_eq!(
+            changes[1].to_string(),
+            "+ New: status Planning, progress 0, rewatched 0"
+        );
+    }
+
+    #[test]
+    fn test_diff_unchanged() {
+        assert!(diff(&[record()], &[record()]).is_empty());
+    }
+
+    #[test_case("list.xml", Some(Format::MalXml); "xml")]
+    #[test_case("list.JSON", Some(Format::Json); "json uppercase")]
+    #[test_case("list.csv", Some(Format::Csv); "csv")]
+    #[test_case("list", None; "no extension")]
+    #[test]
+    fn test_format_from_path(path: &str, expected: Option<Format>) {
+        assert_eq!(Format::from_path(Path::new(path)), expected);
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    anilist::{ListStatus, Review},
+    anime::{AnimeId, EpisodeId},
+    config,
+};
+
+/// A list update that could not reach the tracker.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    id: u32,
+    progress: u32,
+    status: ListStatus,
+    #[serde(flatten)]
+    review: Review,
+    timestamp: u64,
+}
+
+impl Entry {
+    pub fn new(id: AnimeId, progress: EpisodeId, status: ListStatus, review: Review) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Self {
+            id: id.0,
+            progress: progress.0,
+            status,
+            review,
+            timestamp,
+        }
+    }
+
+    pub fn id(&self) -> AnimeId {
+        AnimeId(self.id)
+    }
+
+    pub fn progress(&self) -> EpisodeId {
+        EpisodeId(self.progress)
+    }
+
+    pub fn status(&self) -> ListStatus {
+        self.status
+    }
+
+    pub fn review(&self) -> &Review {
+        &self.review
+    }
+
+    /// Checks if this entry is further on than another one of the same anime.
+    fn is_ahead_of(&self, other: &Entry) -> bool {
+        (self.progress, self.status, self.timestamp)
+            >= (other.progress, other.status, other.timestamp)
+    }
+}
+
+/// Local journal of the pending list updates, replayed once the tracker is reachable.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Journal {
+    entries: Vec<Entry>,
+}
+
+impl Journal {
+    const FILE: &str = "journal.json";
+
+    /// Loads the journal, a missing or unreadable file is an empty journal.
+    pub fn load() -> Self {
+        config::load_file(Self::FILE)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let content = serde_json::to_string_pretty(self)?;
+
+        config::save_file(Self::FILE, &content)
+    }
+
+    /// Adds an entry, keeping only the most advanced update of each anime.
+    pub fn push(&mut self, entry: Entry) {
+        match self.entries.iter_mut().find(|e| e.id == entry.id) {
+            Some(e) if entry.is_ahead_of(e) => *e = entry,
+            Some(_) => (),
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter()
+    }
+
+    /// Removes and returns all the pending entries.
+    pub fn take(&mut self) -> Vec<Entry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use serial_test::file_serial;
+    use simple_test_case::test_case;
+
+    use super::*;
+
+    fn entry(id: u32, progress: u32, status: ListStatus, timestamp: u64) -> Entry {
+        Entry {
+            id,
+            progress,
+            status,
+            review: Review::default(),
+            timestamp,
+        }
+    }
+
+    #[test_case(
+        vec![entry(1, 3, ListStatus::Current, 0), entry(1, 5, ListStatus::Current, 1)],
+        vec![entry(1, 5, ListStatus::Current, 1)];
+        "newer progress replaces"
+    )]
+    #[test_case(
+        vec![entry(1, 5, ListStatus::Current, 0), entry(1, 3, ListStatus::Current, 1)],
+        vec![entry(1, 5, ListStatus::Current, 0)];
+        "progress never goes backwards"
+    )]
+    #[test_case(
+        vec![entry(1, 12, ListStatus::Current, 0), entry(1, 12, ListStatus::Completed, 0)],
+        vec![entry(1, 12, ListStatus::Completed, 0)];
+        "completed wins on same progress"
+    )]
+    #[test_case(
+        vec![entry(1, 3, ListStatus::Current, 0), entry(2, 1, ListStatus::Current, 0)],
+        vec![entry(1, 3, ListStatus::Current, 0), entry(2, 1, ListStatus::Current, 0)];
+        "different anime are kept"
+    )]
+    #[test]
+    fn test_journal_push(entries: Vec<Entry>, expected: Vec<Entry>) {
+        let mut journal = Journal::default();
+        for e in entries {
+            journal.push(e);
+        }
+
+        assert_eq!(journal.take(), expected);
+        assert!(journal.is_empty());
+    }
+
+    #[test]
+    #[file_serial]
+    fn test_journal_save_and_load() {
+        let mut journal = Journal::default();
+        journal.push(Entry::new(
+            AnimeId(42),
+            EpisodeId(3),
+            ListStatus::Current,
+            Review::default(),
+        ));
+        journal.push(Entry::new(
+            AnimeId(7),
+            EpisodeId(12),
+            ListStatus::Completed,
+            Review {
+                score: Some(8.5),
+                notes: Some("Great ending".to_string()),
+                completed_at: NaiveDate::from_ymd_opt(2025, 10, 20),
+            },
+        ));
+        journal.save().unwrap();
+
+        let mut loaded = Journal::load();
+        assert_eq!(loaded.len(), 2);
+        let entries = loaded.take();
+        assert_eq!(entries[0].id(), AnimeId(42));
+        assert_eq!(entries[0].progress(), EpisodeId(3));
+        assert_eq!(entries[1].status(), ListStatus::Completed);
+        assert_eq!(entries[1].review().score, Some(8.5));
+        assert_eq!(entries[1].review().notes.as_deref(), Some("Great ending"));
+
+        loaded.save().unwrap();
+        assert!(Journal::load().is_empty());
+    }
+
+    #[test]
+    fn test_entry_without_review_details() {
+        let json = r#"{"id":1,"progress":3,"status":"CURRENT","score":7.0,"timestamp":0}"#;
+        let entry: Entry = serde_json::from_str(json).unwrap();
+
+        assert_eq!(entry.review().score, Some(7.0));
+        assert_eq!(entry.review().notes, None);
+        assert_eq!(entry.review().completed_at, None);
+    }
+}
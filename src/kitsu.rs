@@ -0,0 +1,715 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, anyhow};
+use reqwest::{
+    Client, RequestBuilder, Response, StatusCode, Url,
+    header::{self, HeaderValue},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::{
+    anilist::{ListStatus, Progress, Review, ScoreFormat, WatchingAnime},
+    anime::{AnimeId, EpisodeId},
+    config,
+    tracker::Tracker,
+    ui::Tui,
+};
+
+const API: &str = "https://kitsu.app/api/edge";
+const OAUTH: &str = "https://kitsu.app/api/oauth/token";
+const JSON_API: &str = "application/vnd.api+json";
+const TABLE: &str = "kitsu";
+const ANILIST_SITE: &str = "anilist/anime";
+
+/// Largest page of the mappings endpoint
+const MAPPINGS_LIMIT: usize = 20;
+/// Largest page of the library entries endpoint
+const LIBRARY_LIMIT: &str = "500";
+
+/// A JSON:API document with a list of resources.
+#[derive(Deserialize, Debug)]
+struct Document {
+    data: Vec<Resource>,
+    #[serde(default)]
+    included: Vec<Resource>,
+    #[serde(default)]
+    links: Links,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Links {
+    next: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Resource {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    attributes: Attributes,
+    #[serde(default)]
+    relationships: HashMap<String, Relationship>,
+}
+
+/// Attributes of the resource types in use, the other ones are ignored.
+#[derive(Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct Attributes {
+    // library entries
+    progress: Option<i64>,
+    status: Option<String>,
+    reconsuming: Option<bool>,
+    reconsume_count: Option<i64>,
+    // anime
+    canonical_title: Option<String>,
+    episode_count: Option<i64>,
+    // mappings
+    external_site: Option<String>,
+    external_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Relationship {
+    data: Option<Linkage>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Linkage {
+    One(Identifier),
+    Many(Vec<Identifier>),
+}
+
+#[derive(Deserialize, Debug)]
+struct Identifier {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+impl Resource {
+    /// Resources linked by a relationship.
+    fn related(&self, name: &str) -> Vec<&Identifier> {
+        match self.relationships.get(name).and_then(|r| r.data.as_ref()) {
+            Some(Linkage::One(identifier)) => vec![identifier],
+            Some(Linkage::Many(identifiers)) => identifiers.iter().collect(),
+            None => vec![],
+        }
+    }
+
+    /// AniList id of a mapping resource.
+    fn anilist_id(&self) -> Option<AnimeId> {
+        if self.attributes.external_site.as_deref() != Some(ANILIST_SITE) {
+            return None;
+        }
+
+        let id = self.attributes.external_id.as_ref()?.parse::<u32>().ok()?;
+
+        Some(id.into())
+    }
+
+    /// Status of a library entry resource.
+    fn status(&self) -> Option<ListStatus> {
+        if self.attributes.reconsuming == Some(true) {
+            return Some(ListStatus::Repeating);
+        }
+
+        match self.attributes.status.as_deref()? {
+            "current" => Some(ListStatus::Current),
+            "completed" => Some(ListStatus::Completed),
+            "on_hold" => Some(ListStatus::Paused),
+            "dropped" => Some(ListStatus::Dropped),
+            "planned" => Some(ListStatus::Planning),
+            _ => None,
+        }
+    }
+}
+
+impl Document {
+    fn find_included(&self, identifier: &Identifier) -> Option<&Resource> {
+        self.included
+            .iter()
+            .find(|r| r.id == identifier.id && r.kind == identifier.kind)
+    }
+
+    /// Library entries of the document, with the anime they refer to.
+    fn entries(&self) -> Vec<Entry> {
+        self.data
+            .iter()
+            .filter_map(|entry| {
+                let anime = self.find_included(entry.related("anime").first()?)?;
+                let anilist_id = anime
+                    .related("mappings")
+                    .into_iter()
+                    .filter_map(|m| self.find_included(m))
+                    .find_map(Resource::anilist_id);
+
+                Some(Entry {
+                    id: entry.id.clone(),
+                    anime_id: anime.id.clone(),
+                    anilist_id,
+                    title: anime.attributes.canonical_title.clone().unwrap_or_default(),
+                    episodes: anime.attributes.episode_count,
+                    progress: entry.attributes.progress.unwrap_or_default(),
+                    status: entry.status(),
+                    repeat: entry.attributes.reconsume_count.unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// A library entry, with the anime details.
+#[derive(Debug)]
+struct Entry {
+    id: String,
+    anime_id: String,
+    anilist_id: Option<AnimeId>,
+    title: String,
+    /// Unknown while the anime is airing
+    episodes: Option<i64>,
+    progress: i64,
+    status: Option<ListStatus>,
+    /// Times the anime has been rewatched
+    repeat: i64,
+}
+
+impl Entry {
+    fn progress(&self) -> Progress {
+        let episodes = self.episodes.unwrap_or_default();
+        let progress = Progress::new(episodes, self.progress, self.title.clone());
+
+        progress.with_status(self.status, self.repeat)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Tokens {
+    access_token: String,
+    refresh_token: String,
+}
+
+/// Kitsu API client, logged in with the OAuth password grant.
+#[derive(Default, Debug)]
+pub struct Kitsu {
+    client: Mutex<Client>,
+    user_id: String,
+}
+
+impl Kitsu {
+    pub async fn new() -> Result<Self> {
+        let token = match config::load(TABLE, "token") {
+            Ok(token) => token,
+            Err(_) => login().await?,
+        };
+
+        let mut kitsu = Self {
+            client: Mutex::new(build_client(&token)?),
+            ..Default::default()
+        };
+        kitsu.user_id = kitsu.get_user_id().await?;
+
+        Ok(kitsu)
+    }
+
+    /// Sends a request, when the token is rejected it is refreshed (or a new
+    /// login is asked) and the request is sent again.
+    async fn send(&self, request: impl Fn(&Client) -> RequestBuilder) -> Result<Response> {
+        let response = request(&self.client()?).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response.error_for_status()?);
+        }
+
+        let token = match refresh().await {
+            Ok(token) => token,
+            Err(_) => {
+                eprintln!("The Kitsu token is expired, please log in again");
+                login().await?
+            }
+        };
+        *self
+            .client
+            .lock()
+            .map_err(|_| anyhow!("Unable to create client"))? = build_client(&token)?;
+
+        Ok(request(&self.client()?).send().await?.error_for_status()?)
+    }
+
+    fn client(&self) -> Result<Client> {
+        let client = self
+            .client
+            .lock()
+            .map_err(|_| anyhow!("Unable to create client"))?;
+
+        Ok(client.clone())
+    }
+
+    async fn get(&self, url: &str) -> Result<Document> {
+        Ok(self.send(|c| c.get(url)).await?.json().await?)
+    }
+
+    async fn get_user_id(&self) -> Result<String> {
+        let url = format!("{API}/users?filter[self]=true");
+        let user = self.get(&url).await?.data.into_iter().next();
+
+        user.map(|u| u.id).context("Unable to get the Kitsu user")
+    }
+
+    /// Library entries of the user, following the pages.
+    async fn get_entries(&self, filters: &[(&str, &str)]) -> Result<Vec<Entry>> {
+        let mut params = vec![
+            ("filter[userId]", self.user_id.as_str()),
+            ("filter[kind]", "anime"),
+            ("include", "anime.mappings"),
+            ("page[limit]", LIBRARY_LIMIT),
+        ];
+        params.extend_from_slice(filters);
+
+        let url = format!("{API}/library-entries");
+        let mut next = Some(Url::parse_with_params(&url, params)?.to_string());
+        let mut entries = Vec::new();
+        while let Some(url) = next {
+            let document = self.get(&url).await?;
+
+            entries.extend(document.entries());
+            next = document.links.next;
+        }
+
+        Ok(entries)
+    }
+
+    /// Library entry of the anime, `None` when it is not in the library.
+    async fn get_entry(&self, anime_id: &str) -> Result<Option<Entry>> {
+        let entries = self.get_entries(&[("filter[animeId]", anime_id)]).await?;
+
+        Ok(entries.into_iter().next())
+    }
+
+    /// Kitsu ids of the given anime, missing the ones without a mapping.
+    async fn get_kitsu_ids(&self, ids: &[AnimeId]) -> HashMap<AnimeId, String> {
+        let mut kitsu_ids = HashMap::new();
+        for chunk in ids.chunks(MAPPINGS_LIMIT) {
+            let ids: Vec<_> = chunk.iter().map(|id| id.to_string()).collect();
+            let params = [
+                ("filter[externalSite]", ANILIST_SITE),
+                ("filter[externalId]", &ids.join(",")),
+                ("include", "item"),
+            ];
+
+            let url = format!("{API}/mappings");
+            let Ok(url) = Url::parse_with_params(&url, params) else {
+                continue;
+            };
+            let Ok(document) = self.get(url.as_str()).await else {
+                continue;
+            };
+
+            kitsu_ids.extend(get_mapped_ids(&document));
+        }
+
+        kitsu_ids
+    }
+
+    async fn get_kitsu_id(&self, id: AnimeId) -> Result<String> {
+        self.get_kitsu_ids(&[id])
+            .await
+            .remove(&id)
+            .context(format!("anime {id} is not on Kitsu"))
+    }
+
+    /// Updates the library entry, creating it when missing.
+    async fn put(&self, entry: Option<&Entry>, anime_id: &str, attributes: Value) -> Result<()> {
+        let body = match entry {
+            Some(entry) => json!({
+                "data": {
+                    "id": entry.id,
+                    "type": "libraryEntries",
+                    "attributes": attributes,
+                }
+            }),
+            None => json!({
+                "data": {
+                    "type": "libraryEntries",
+                    "attributes": attributes,
+                    "relationships": {
+                        "user": { "data": { "type": "users", "id": self.user_id } },
+                        "anime": { "data": { "type": "anime", "id": anime_id } },
+                    },
+                }
+            }),
+        };
+
+        let body = body.to_string();
+        self.send(|c| {
+            let request = match entry {
+                Some(entry) => c.patch(format!("{API}/library-entries/{}", entry.id)),
+                None => c.post(format!("{API}/library-entries")),
+            };
+
+            request
+                .header(header::CONTENT_TYPE, JSON_API)
+                .body(body.clone())
+        })
+        .await?;
+
+        Ok(())
+    }
+}
+
+impl Tracker for Kitsu {
+    async fn get_watching_list(&self) -> Option<Vec<WatchingAnime>> {
+        let entries = self
+            .get_entries(&[("filter[status]", "current")])
+            .await
+            .ok()?;
+
+        let mut list: Vec<_> = entries
+            .into_iter()
+            .filter_map(|entry| {
+                // shown as unknown while the anime is airing
+                let watched = entry
+                    .episodes
+                    .map_or(0, |episodes| episodes - entry.progress);
+
+                Some(WatchingAnime::new(entry.anilist_id?, entry.title, watched))
+            })
+            .collect();
+
+        list.sort_by(|a, b| a.title().cmp(b.title()));
+
+        Some(list)
+    }
+
+    async fn get_progress(&self, id: AnimeId) -> Option<Progress> {
+        let anime_id = self.get_kitsu_id(id).await.ok()?;
+
+        Some(self.get_entry(&anime_id).await.ok()??.progress())
+    }
+
+    async fn get_progress_batch(&self, ids: &[AnimeId]) -> HashMap<AnimeId, Progress> {
+        let kitsu_ids = self.get_kitsu_ids(ids).await;
+        if kitsu_ids.is_empty() {
+            return HashMap::new();
+        }
+
+        let filter: Vec<_> = kitsu_ids.values().map(String::as_str).collect();
+        let filter = filter.join(",");
+        let Ok(entries) = self.get_entries(&[("filter[animeId]", &filter)]).await else {
+            return HashMap::new();
+        };
+
+        entries
+            .into_iter()
+            .filter_map(|entry| {
+                let id = kitsu_ids
+                    .iter()
+                    .find_map(|(id, kitsu_id)| (*kitsu_id == entry.anime_id).then_some(*id))?;
+
+                Some((id, entry.progress()))
+            })
+            .collect()
+    }
+
+    async fn update(&mut self, id: AnimeId, number: EpisodeId) -> Result<()> {
+        let anime_id = self.get_kitsu_id(id).await?;
+        let entry = self.get_entry(&anime_id).await?;
+
+        let (status, repeat) = match entry.as_ref().map(Entry::progress) {
+            Some(p) => match p.status_after(number) {
+                Some(status) => (status, p.repeat_after(number)),
+                None => return Ok(()),
+            },
+            None => (ListStatus::Current, None),
+        };
+
+        let attributes = entry_attributes(status, number, repeat, Review::default());
+        self.put(entry.as_ref(), &anime_id, attributes).await
+    }
+
+    async fn complete(&mut self, id: AnimeId, number: EpisodeId, review: Review) -> Result<()> {
+        let anime_id = self.get_kitsu_id(id).await?;
+        let entry = self.get_entry(&anime_id).await?;
+
+        let repeat = entry
+            .as_ref()
+            .map(Entry::progress)
+            .and_then(|p| p.repeat_after(number));
+
+        let attributes = entry_attributes(ListStatus::Completed, number, repeat, review);
+        self.put(entry.as_ref(), &anime_id, attributes).await
+    }
+
+    async fn rewatch(&mut self, id: AnimeId) -> Result<()> {
+        let anime_id = self.get_kitsu_id(id).await?;
+        let entry = self.get_entry(&anime_id).await?;
+
+        let attributes =
+            entry_attributes(ListStatus::Repeating, EpisodeId(0), None, Review::default());
+        self.put(entry.as_ref(), &anime_id, attributes).await
+    }
+
+    /// Ratings are shown in half points out of ten by default.
+    async fn score_format(&self) -> ScoreFormat {
+        ScoreFormat::Point10Decimal
+    }
+}
+
+/// Attributes of a library entry update, a rewatch is a current entry being
+/// reconsumed.
+fn entry_attributes(
+    status: ListStatus,
+    number: EpisodeId,
+    repeat: Option<i64>,
+    review: Review,
+) -> Value {
+    let (status, reconsuming) = match status {
+        ListStatus::Repeating => ("current", true),
+        ListStatus::Completed => ("completed", false),
+        _ => ("current", false),
+    };
+
+    let number = u32::from(number);
+    let mut attributes = json!({
+        "status": status,
+        "progress": number,
+        "reconsuming": reconsuming,
+    });
+    if let Some(repeat) = repeat {
+        attributes["reconsumeCount"] = repeat.into();
+    }
+    if let Some(rating) = review.score.and_then(to_rating_twenty) {
+        attributes["ratingTwenty"] = rating.into();
+    }
+    if let Some(notes) = review.notes {
+        attributes["notes"] = notes.into();
+    }
+    if let Some(date) = review.completed_at {
+        attributes["finishedAt"] = format!("{date}T00:00:00Z").into();
+    }
+
+    attributes
+}
+
+/// Maps the AniList ids of a mappings document to the Kitsu anime ids.
+fn get_mapped_ids(document: &Document) -> HashMap<AnimeId, String> {
+    document
+        .data
+        .iter()
+        .filter_map(|mapping| {
+            let id = mapping.anilist_id()?;
+            let anime = mapping.related("item").into_iter().next()?;
+
+            Some((id, anime.id.clone()))
+        })
+        .collect()
+}
+
+fn build_client(token: &str) -> Result<Client> {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(header::ACCEPT, HeaderValue::from_static(JSON_API));
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| anyhow!("Invalid token"))?,
+    );
+
+    Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|_| anyhow!("Unable to create client"))
+}
+
+async fn login() -> Result<String> {
+    let (username, password) = Tui::get_credentials("Kitsu")?;
+
+    request_token(&[
+        ("grant_type", "password"),
+        ("username", &username),
+        ("password", &password),
+    ])
+    .await
+}
+
+async fn refresh() -> Result<String> {
+    let refresh_token = config::load(TABLE, "refresh_token")?;
+
+    request_token(&[
+        ("grant_type", "refresh_token"),
+        ("refresh_token", &refresh_token),
+    ])
+    .await
+}
+
+/// Asks the tokens and saves them, returning the access token.
+async fn request_token(form: &[(&str, &str)]) -> Result<String> {
+    let tokens: Tokens = Client::new()
+        .post(OAUTH)
+        .form(form)
+        .send()
+        .await?
+        .error_for_status()
+        .context("Kitsu login failed")?
+        .json()
+        .await?;
+
+    config::save(TABLE, "token", &tokens.access_token)?;
+    config::save(TABLE, "refresh_token", &tokens.refresh_token)?;
+
+    Ok(tokens.access_token)
+}
+
+/// Kitsu ratings go from 2 to 20, a zero score is no rating.
+fn to_rating_twenty(score: f64) -> Option<u8> {
+    if score <= 0.0 {
+        return None;
+    }
+
+    Some((score * 2.0).round().clamp(2.0, 20.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    const LIBRARY: &str = r#"{
+        "data": [
+            {
+                "id": "101",
+                "type": "libraryEntries",
+                "attributes": {"status": "current", "progress": 12},
+                "relationships": {"anime": {"data": {"type": "anime", "id": "46474"}}}
+            },
+            {
+                "id": "102",
+                "type": "libraryEntries",
+                "attributes": {"status": "current", "progress": 3, "reconsuming": true, "reconsumeCount": 1},
+                "relationships": {"anime": {"data": {"type": "anime", "id": "48363"}}}
+            }
+        ],
+        "included": [
+            {
+                "id": "46474",
+                "type": "anime",
+                "attributes": {"canonicalTitle": "Sousou no Frieren", "episodeCount": 28},
+                "relationships": {
+                    "mappings": {"data": [{"type": "mappings", "id": "1"}, {"type": "mappings", "id": "2"}]}
+                }
+            },
+            {
+                "id": "48363",
+                "type": "anime",
+                "attributes": {"canonicalTitle": "Dandadan", "episodeCount": null},
+                "relationships": {"mappings": {"links": {}}}
+            },
+            {
+                "id": "1",
+                "type": "mappings",
+                "attributes": {"externalSite": "myanimelist/anime", "externalId": "52991"}
+            },
+            {
+                "id": "2",
+                "type": "mappings",
+                "attributes": {"externalSite": "anilist/anime", "externalId": "154587"}
+            }
+        ],
+        "links": {"next": "https://kitsu.app/api/edge/library-entries?page%5Boffset%5D=2"}
+    }"#;
+
+    const MAPPINGS: &str = r#"{
+        "data": [
+            {
+                "id": "2",
+                "type": "mappings",
+                "attributes": {"externalSite": "anilist/anime", "externalId": "154587"},
+                "relationships": {"item": {"data": {"type": "anime", "id": "46474"}}}
+            },
+            {
+                "id": "3",
+                "type": "mappings",
+                "attributes": {"externalSite": "anilist/anime", "externalId": "not a number"},
+                "relationships": {"item": {"data": {"type": "anime", "id": "1"}}}
+            }
+        ],
+        "included": [{"id": "46474", "type": "anime", "attributes": {}}]
+    }"#;
+
+    #[test]
+    fn test_document_entries() {
+        let document: Document = serde_json::from_str(LIBRARY).unwrap();
+        let entries = document.entries();
+
+        assert_eq!(entries.len(), 2);
+        assert!(document.links.next.is_some());
+
+        let frieren = &entries[0];
+        assert_eq!(frieren.id, "101");
+        assert_eq!(frieren.anime_id, "46474");
+        assert_eq!(frieren.anilist_id, Some(AnimeId(154587)));
+        assert_eq!(frieren.episodes, Some(28));
+        assert_eq!(frieren.progress, 12);
+
+        let dandadan = &entries[1];
+        assert_eq!(dandadan.title, "Dandadan");
+        assert_eq!(dandadan.status, Some(ListStatus::Repeating));
+        assert_eq!(dandadan.repeat, 1);
+        assert_eq!(dandadan.anilist_id, None);
+        assert_eq!(dandadan.episodes, None);
+    }
+
+    #[test]
+    fn test_entry_progress() {
+        let document: Document = serde_json::from_str(LIBRARY).unwrap();
+        let entries = document.entries();
+
+        let progress = entries[0].progress();
+        assert_eq!(progress.latest(), EpisodeId(12));
+        assert!(progress.is_last(EpisodeId(28)));
+        assert_eq!(progress.title(), "Sousou no Frieren");
+
+        // airing series without a known total
+        let progress = entries[1].progress();
+        assert_eq!(progress.title(), "Dandadan");
+        assert!(!progress.is_last(EpisodeId(12)));
+    }
+
+    #[test]
+    fn test_get_mapped_ids() {
+        let document: Document = serde_json::from_str(MAPPINGS).unwrap();
+        let ids = get_mapped_ids(&document);
+
+        assert_eq!(ids.len(), 1);
+        assert_eq!(ids.get(&AnimeId(154587)).map(String::as_str), Some("46474"));
+    }
+
+    #[test]
+    fn test_entry_attributes() {
+        let rewatch =
+            entry_attributes(ListStatus::Repeating, EpisodeId(0), None, Review::default());
+        assert_eq!(
+            rewatch,
+            json!({ "status": "current", "progress": 0, "reconsuming": true })
+        );
+
+        let review = Review {
+            score: Some(8.0),
+            ..Default::default()
+        };
+        let complete = entry_attributes(ListStatus::Completed, EpisodeId(28), Some(2), review);
+        assert_eq!(complete["status"], "completed");
+        assert_eq!(complete["reconsumeCount"], 2);
+        assert_eq!(complete["ratingTwenty"], 16);
+    }
+
+    #[test_case(0.0, None; "no score")]
+    #[test_case(8.0, Some(16); "integer score")]
+    #[test_case(7.3, Some(15); "rounded score")]
+    #[test_case(0.5, Some(2); "lowest rating")]
+    #[test_case(85.0, Some(20); "hundred point score clamped")]
+    #[test]
+    fn test_to_rating_twenty(score: f64, expected: Option<u8>) {
+        assert_eq!(to_rating_twenty(score), expected);
+    }
+}
@@ -2,12 +2,22 @@ mod anilist;
 mod anime;
 mod app;
 mod archives;
+mod cache;
 mod cli;
 mod config;
 mod error;
+mod export;
+mod journal;
+mod kitsu;
+#[cfg(test)]
+mod mock;
+mod myanimelist;
+mod players;
 mod proxy;
 mod range;
 mod scraper;
+mod sync;
+mod tracker;
 mod ui;
 
 use cli::{Args, Parser};
@@ -0,0 +1,397 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use reqwest::Url;
+use serde_json::{Value, json};
+
+/// Folder the pages are saved in as fixtures while they are fetched.
+static RECORDING: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// A request received by a local server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub path: String,
+    /// Header names are lowercase
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// A response sent by a local server.
+struct Reply {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+/// Answers the requests in background, keeping them to be checked later.
+fn serve(
+    handler: impl Fn(&Request) -> Reply + Send + 'static,
+) -> (String, Arc<Mutex<Vec<Request>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Unable to start the mock server");
+    let url = format!("http://{}", listener.local_addr().unwrap());
+
+    let requests = Arc::new(Mutex::new(vec![]));
+    let received = Arc::clone(&requests);
+
+    thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let Some(request) = read_request(&stream) else {
+                continue;
+            };
+
+            let reply = handler(&request);
+            received.lock().unwrap().push(request);
+            write_reply(&mut stream, &reply);
+        }
+    });
+
+    (url, requests)
+}
+
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    let path = line.split_whitespace().nth(1)?.to_string();
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.insert(name.to_ascii_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let length = headers
+        .get("content-length")
+        .and_then(|l| l.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        path,
+        headers,
+        body,
+    })
+}
+
+fn write_reply(stream: &mut TcpStream, reply: &Reply) {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        reply.status,
+        reply.content_type,
+        reply.body.len()
+    );
+
+    stream.write_all(head.as_bytes()).ok();
+    stream.write_all(reply.body.as_bytes()).ok();
+}
+
+/// A GraphQL server answering each operation with a canned response, to test
+/// the AniList requests end to end.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockServer {
+    /// Starts the server in background, operations without a response get
+    /// a GraphQL error. The responses given more times for an operation are
+    /// sent in order, the last one is repeated.
+    pub fn start(responses: Vec<(&str, Value)>) -> Self {
+        let mut queues: HashMap<String, VecDeque<Value>> = HashMap::new();
+        for (name, response) in responses {
+            queues
+                .entry(name.to_string())
+                .or_default()
+                .push_back(response);
+        }
+        let responses = Mutex::new(queues);
+
+        let (url, requests) = serve(move |request| {
+            let body: Value = serde_json::from_slice(&request.body).unwrap_or_default();
+            let operation = body["operationName"].as_str().unwrap_or_default();
+            let response = match responses.lock().unwrap().get_mut(operation) {
+                Some(queue) if queue.len() > 1 => queue.pop_front(),
+                Some(queue) => queue.front().cloned(),
+                None => None,
+            };
+            let response = response.unwrap_or_else(|| error(&format!("unexpected {operation}")));
+
+            Reply {
+                status: "200 OK",
+                content_type: "application/json",
+                body: response.to_string(),
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Variables of the requests of an operation, in the order they were received.
+    pub fn variables(&self, operation: &str) -> Vec<Value> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|request| serde_json::from_slice::<Value>(&request.body).ok())
+            .filter(|body| body["operationName"] == operation)
+            .map(|body| body["variables"].clone())
+            .collect()
+    }
+}
+
+/// Serves the pages recorded from an archive, missing ones are not found.
+pub struct FixtureServer {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl FixtureServer {
+    pub fn start(archive: &str) -> Self {
+        let dir = fixtures_dir(archive);
+
+        let (url, requests) =
+            serve(
+                move |request| match fs::read_to_string(dir.join(fixture_name(&request.path))) {
+                    Ok(body) => Reply {
+                        status: "200 OK",
+                        content_type: "text/html",
+                        body,
+                    },
+                    Err(_) => Reply {
+                        status: "404 Not Found",
+                        content_type: "text/plain",
+                        body: String::new(),
+                    },
+                },
+            );
+
+        Self { url, requests }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// Folder of the pages recorded from an archive.
+pub fn fixtures_dir(archive: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(archive)
+}
+
+/// File a page is recorded in, from its path and query (eg.
+/// `/search?keyword=bunny+girl` is `search_keyword_bunny_girl.html`).
+pub fn fixture_name(path: &str) -> String {
+    let name: String = path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    format!("{}.html", name.trim_matches('_'))
+}
+
+/// Saves the pages fetched in the folder, to refresh the fixtures of an
+/// archive when the site changes.
+pub fn set_recording(dir: Option<PathBuf>) {
+    *RECORDING.lock().unwrap() = dir;
+}
+
+/// Saves the page when recording, see `set_recording`.
+pub fn record(url: &str, page: &str) {
+    let Some(dir) = RECORDING.lock().unwrap().clone() else {
+        return;
+    };
+    let Ok(url) = Url::parse(url) else {
+        return;
+    };
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    fs::create_dir_all(&dir).ok();
+    fs::write(dir.join(fixture_name(&path)), page).ok();
+}
+
+/// A GraphQL error response, eg. for a rate limited request.
+pub fn error(message: &str) -> Value {
+    json!({ "data": null, "errors": [{ "message": message }] })
+}
+
+/// Response of `RelationsQuery` for an anime with its sequels, as
+/// `(id, romaji title, start year)`.
+pub fn relations(media: (i64, &str, i64), sequels: &[(i64, &str, i64)]) -> Value {
+    let node = |&(id, title, year): &(i64, &str, i64)| {
+        json!({
+            "id": id,
+            "type": "ANIME",
+            "format": "TV",
+            "seasonYear": year,
+            "episodes": 12,
+            "synonyms": [],
+            "startDate": { "year": year, "month": 1, "day": 1 },
+            "title": { "romaji": title, "english": null, "native": null }
+        })
+    };
+    let edges: Vec<_> = sequels
+        .iter()
+        .map(|sequel| json!({ "relationType": "SEQUEL", "node": node(sequel) }))
+        .collect();
+
+    let mut media = node(&media);
+    media["relations"] = json!({ "edges": edges });
+
+    json!({ "data": { "Media": media } })
+}
+
+/// Response of `UserQuery`.
+pub fn viewer(id: i64, name: &str) -> Value {
+    json!({
+        "data": {
+            "Viewer": {
+                "id": id,
+                "name": name,
+                "mediaListOptions": { "scoreFormat": "POINT_10" }
+            }
+        }
+    })
+}
+
+/// Entries of `WatchingQuery` and of the other list queries, as
+/// `(id, romaji title, progress, episodes)`.
+pub type Entry<'a> = (i64, &'a str, i64, i64);
+
+/// Response of `WatchingQuery` with a single `Watching` list.
+pub fn watching(entries: &[Entry]) -> Value {
+    lists(&[("Watching", entries)])
+}
+
+/// Response of `WatchingQuery` with the named lists, eg. custom ones.
+pub fn lists(lists: &[(&str, &[Entry])]) -> Value {
+    let lists: Vec<_> = lists
+        .iter()
+        .map(|(name, entries)| {
+            let entries: Vec<_> = entries
+                .iter()
+                .map(|&(id, title, progress, episodes)| {
+                    json!({
+                        "progress": progress,
+                        "media": {
+                            "id": id,
+                            "episodes": episodes,
+                            "synonyms": [],
+                            "title": { "romaji": title, "english": null, "native": null },
+                            "nextAiringEpisode": null
+                        }
+                    })
+                })
+                .collect();
+
+            json!({ "name": name, "entries": entries })
+        })
+        .collect();
+
+    json!({
+        "data": {
+            "MediaListCollection": { "lists": lists }
+        }
+    })
+}
+
+/// Response of `ProgressQuery` for an anime in the list.
+pub fn progress(title: &str, episodes: i64, progress: i64, status: &str) -> Value {
+    json!({
+        "data": {
+            "Media": {
+                "episodes": episodes,
+                "title": { "romaji": title, "english": null, "native": null },
+                "mediaListEntry": { "progress": progress, "status": status, "repeat": 0 }
+            }
+        }
+    })
+}
+
+/// Response of `ProgressBatchQuery` with a single page, as
+/// `(id, romaji title, progress, episodes)`, unknown episodes while airing.
+pub fn progress_batch(media: &[(i64, &str, i64, Option<i64>)]) -> Value {
+    let media: Vec<_> = media
+        .iter()
+        .map(|&(id, title, progress, episodes)| {
+            json!({
+                "id": id,
+                "episodes": episodes,
+                "title": { "romaji": title, "english": null, "native": null },
+                "mediaListEntry": { "progress": progress, "status": "CURRENT", "repeat": 0 }
+            })
+        })
+        .collect();
+
+    json!({
+        "data": {
+            "Page": { "pageInfo": { "hasNextPage": false }, "media": media }
+        }
+    })
+}
+
+/// Response of `ProgressMutation`.
+pub fn saved() -> Value {
+    json!({
+        "data": { "SaveMediaListEntry": { "__typename": "MediaList" } }
+    })
+}
+
+/// Response of `NotificationsQuery` with a page of airing notifications,
+/// newest first, as `(anime id, romaji title, episode, creation time)`.
+pub fn notifications(aired: &[(i64, &str, i64, i64)], has_next_page: bool) -> Value {
+    let notifications: Vec<_> = aired
+        .iter()
+        .map(|&(id, title, episode, created_at)| {
+            json!({
+                "__typename": "AiringNotification",
+                "episode": episode,
+                "createdAt": created_at,
+                "media": {
+                    "id": id,
+                    "episodes": 12,
+                    "synonyms": [],
+                    "title": { "romaji": title, "english": null, "native": null }
+                }
+            })
+        })
+        .collect();
+
+    json!({
+        "data": {
+            "Page": {
+                "pageInfo": { "hasNextPage": has_next_page },
+                "notifications": notifications
+            }
+        }
+    })
+}
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{Context, Result, anyhow};
+use rand::distr::{Alphanumeric, SampleString};
+use reqwest::{
+    Client, RequestBuilder, Response, StatusCode, Url,
+    header::{self, HeaderValue},
+};
+use serde::Deserialize;
+
+use crate::{
+    anilist::{Anilist, ListStatus, Progress, Review, ScoreFormat, WatchingAnime},
+    anime::{AnimeId, EpisodeId},
+    config,
+    tracker::Tracker,
+    ui::Tui,
+};
+
+const API: &str = "https://api.myanimelist.net/v2";
+const OAUTH: &str = "https://myanimelist.net/v1/oauth2";
+const TABLE: &str = "myanimelist";
+
+#[derive(Deserialize, Debug)]
+struct AnimeList {
+    data: Vec<ListItem>,
+    #[serde(default)]
+    paging: Paging,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Paging {
+    next: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ListItem {
+    node: Node,
+    list_status: MyListStatus,
+}
+
+#[derive(Deserialize, Debug)]
+struct Node {
+    id: u32,
+    title: String,
+    /// Zero while the total is unknown
+    #[serde(default)]
+    num_episodes: i64,
+    my_list_status: Option<MyListStatus>,
+}
+
+#[derive(Deserialize, Debug)]
+struct MyListStatus {
+    status: Option<String>,
+    #[serde(default)]
+    num_episodes_watched: i64,
+    #[serde(default)]
+    is_rewatching: bool,
+    #[serde(default)]
+    num_times_rewatched: i64,
+}
+
+impl MyListStatus {
+    fn status(&self) -> Option<ListStatus> {
+        if self.is_rewatching {
+            return Some(ListStatus::Repeating);
+        }
+
+        match self.status.as_deref()? {
+            "watching" => Some(ListStatus::Current),
+            "completed" => Some(ListStatus::Completed),
+            "on_hold" => Some(ListStatus::Paused),
+            "dropped" => Some(ListStatus::Dropped),
+            "plan_to_watch" => Some(ListStatus::Planning),
+            _ => None,
+        }
+    }
+}
+
+impl Node {
+    /// Episodes left to watch, shown as unknown while the anime is airing.
+    fn episodes_left(&self, status: &MyListStatus) -> i64 {
+        if self.num_episodes == 0 {
+            0
+        } else {
+            self.num_episodes - status.num_episodes_watched
+        }
+    }
+
+    /// The total is zero while the anime is airing, as `Progress` expects.
+    fn progress(self, status: &MyListStatus) -> Progress {
+        Progress::new(self.num_episodes, status.num_episodes_watched, self.title)
+            .with_status(status.status(), status.num_times_rewatched)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct Tokens {
+    access_token: String,
+    refresh_token: String,
+}
+
+/// MyAnimeList API v2 client, logged in with OAuth PKCE.
+#[derive(Default, Debug)]
+pub struct MyAnimeList {
+    client: Mutex<Client>,
+    client_id: String,
+    /// Public AniList client, to map the ids to the MyAnimeList ones
+    anilist: Anilist,
+}
+
+impl MyAnimeList {
+    pub async fn new() -> Result<Self> {
+        let client_id = config::load(TABLE, "client_id")
+            .context("Missing MyAnimeList client id, set `myanimelist.client_id`")?;
+        let token = match config::load(TABLE, "token") {
+            Ok(token) => token,
+            Err(_) => login(&client_id).await?,
+        };
+
+        Ok(Self {
+            client: Mutex::new(build_client(&token)?),
+            client_id,
+            anilist: Anilist::anonymous()?,
+        })
+    }
+
+    /// Sends a request, when the token is rejected it is refreshed (or a new
+    /// login is asked) and the request is sent again.
+    async fn send(&self, request: impl Fn(&Client) -> RequestBuilder) -> Result<Response> {
+        let response = request(&self.client()?).send().await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response.error_for_status()?);
+        }
+
+        let token = match refresh(&self.client_id).await {
+            Ok(token) => token,
+            Err(_) => {
+                eprintln!("The MyAnimeList token is expired, please log in again");
+                login(&self.client_id).await?
+            }
+        };
+        *self
+            .client
+            .lock()
+            .map_err(|_| anyhow!("Unable to create client"))? = build_client(&token)?;
+
+        Ok(request(&self.client()?).send().await?.error_for_status()?)
+    }
+
+    fn client(&self) -> Result<Client> {
+        let client = self
+            .client
+            .lock()
+            .map_err(|_| anyhow!("Unable to create client"))?;
+
+        Ok(client.clone())
+    }
+
+    /// Entries of the user list, only the ones with the given status when set.
+    async fn get_list(&self, status: Option<&str>) -> Option<Vec<ListItem>> {
+        let mut params = vec![("fields", "list_status,num_episodes"), ("limit", "1000")];
+        if let Some(status) = status {
+            params.push(("status", status));
+        }
+
+        let url = format!("{API}/users/@me/animelist");
+        let mut next = Some(Url::parse_with_params(&url, params).ok()?.to_string());
+        let mut items = Vec::new();
+        while let Some(url) = next {
+            let response = self.send(|c| c.get(&url)).await.ok()?;
+            let page: AnimeList = response.json().await.ok()?;
+
+            items.extend(page.data);
+            next = page.paging.next;
+        }
+
+        Some(items)
+    }
+
+    async fn get_mal_id(&self, id: AnimeId) -> Option<u32> {
+        self.anilist.get_mal_ids(&[id]).await.get(&id).copied()
+    }
+
+    async fn put(&self, mal_id: u32, form: Vec<(&str, String)>) -> Result<()> {
+        let url = format!("{API}/anime/{mal_id}/my_list_status");
+        self.send(|c| c.patch(&url).form(&form)).await?;
+
+        Ok(())
+    }
+}
+
+impl Tracker for MyAnimeList {
+    async fn get_watching_list(&self) -> Option<Vec<WatchingAnime>> {
+        // rewatched anime keep the completed status
+        let items: Vec<_> = self
+            .get_list(None)
+            .await?
+            .into_iter()
+            .filter(|i| {
+                let status = i.list_status.status();
+                status == Some(ListStatus::Current) || status == Some(ListStatus::Repeating)
+            })
+            .collect();
+        let mal_ids: Vec<_> = items.iter().map(|i| i.node.id).collect();
+        let ids = self.anilist.get_anilist_ids(&mal_ids).await;
+
+        let mut list: Vec<_> = items
+            .into_iter()
+            .filter_map(|item| {
+                let id = *ids.get(&item.node.id)?;
+                let watched = item.node.episodes_left(&item.list_status);
+
+                Some(WatchingAnime::new(id, item.node.title, watched))
+            })
+            .collect();
+
+        list.sort_by(|a, b| a.title().cmp(b.title()));
+
+        Some(list)
+    }
+
+    async fn get_progress(&self, id: AnimeId) -> Option<Progress> {
+        let mal_id = self.get_mal_id(id).await?;
+        let url = Url::parse_with_params(
+            &format!("{API}/anime/{mal_id}"),
+            [("fields", "num_episodes,my_list_status")],
+        )
+        .ok()?;
+
+        let response = self.send(|c| c.get(url.clone())).await.ok()?;
+        let mut node: Node = response.json().await.ok()?;
+        let status = node.my_list_status.take()?;
+
+        Some(node.progress(&status))
+    }
+
+    async fn get_progress_batch(&self, ids: &[AnimeId]) -> HashMap<AnimeId, Progress> {
+        // the whole list takes fewer requests than an anime at a time
+        let mal_ids: HashMap<_, _> = self
+            .anilist
+            .get_mal_ids(ids)
+            .await
+            .into_iter()
+            .map(|(id, mal_id)| (mal_id, id))
+            .collect();
+        if mal_ids.is_empty() {
+            return HashMap::new();
+        }
+
+        let Some(items) = self.get_list(None).await else {
+            return HashMap::new();
+        };
+
+        items
+            .into_iter()
+            .filter_map(|item| {
+                let id = *mal_ids.get(&item.node.id)?;
+                let progress = item.node.progress(&item.list_status);
+
+                Some((id, progress))
+            })
+            .collect()
+    }
+
+    async fn update(&mut self, id: AnimeId, number: EpisodeId) -> Result<()> {
+        let mal_id = self
+            .get_mal_id(id)
+            .await
+            .context(format!("anime {id} is not on MyAnimeList"))?;
+
+        let (status, repeat) = match self.get_progress(id).await {
+            Some(p) => match p.status_after(number) {
+                Some(status) => (status, p.repeat_after(number)),
+                None => return Ok(()),
+            },
+            None => (ListStatus::Current, None),
+        };
+
+        let form = update_form(status, number, repeat, Review::default());
+        self.put(mal_id, form).await
+    }
+
+    async fn complete(&mut self, id: AnimeId, number: EpisodeId, review: Review) -> Result<()> {
+        let mal_id = self
+            .get_mal_id(id)
+            .await
+            .context(format!("anime {id} is not on MyAnimeList"))?;
+
+        let repeat = self
+            .get_progress(id)
+            .await
+            .and_then(|p| p.repeat_after(number));
+
+        let form = update_form(ListStatus::Completed, number, repeat, review);
+        self.put(mal_id, form).await
+    }
+
+    async fn rewatch(&mut self, id: AnimeId) -> Result<()> {
+        let mal_id = self
+            .get_mal_id(id)
+            .await
+            .context(format!("anime {id} is not on MyAnimeList"))?;
+
+        let form = update_form(ListStatus::Repeating, EpisodeId(0), None, Review::default());
+        self.put(mal_id, form).await
+    }
+
+    async fn score_format(&self) -> ScoreFormat {
+        ScoreFormat::Point10
+    }
+}
+
+/// Fields of a list status update, a rewatch keeps the completed status.
+fn update_form(
+    status: ListStatus,
+    number: EpisodeId,
+    repeat: Option<i64>,
+    review: Review,
+) -> Vec<(&'static str, String)> {
+    let (status, rewatching) = match status {
+        ListStatus::Repeating => ("completed", true),
+        ListStatus::Completed => ("completed", false),
+        _ => ("watching", false),
+    };
+
+    let mut form = vec![
+        ("status", status.to_string()),
+        ("is_rewatching", rewatching.to_string()),
+        ("num_watched_episodes", number.to_string()),
+    ];
+    if let Some(repeat) = repeat {
+        form.push(("num_times_rewatched", repeat.to_string()));
+    }
+    if let Some(score) = review.score {
+        form.push(("score", to_mal_score(score).to_string()));
+    }
+    if let Some(notes) = review.notes {
+        form.push(("comments", notes));
+    }
+    if let Some(date) = review.completed_at {
+        form.push(("finish_date", date.to_string()));
+    }
+
+    form
+}
+
+fn build_client(token: &str) -> Result<Client> {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        HeaderValue::from_str(&format!("Bearer {token}")).map_err(|_| anyhow!("Invalid token"))?,
+    );
+
+    Client::builder()
+        .default_headers(headers)
+        .build()
+        .map_err(|_| anyhow!("Unable to create client"))
+}
+
+/// Logs in with the authorization code flow, using the plain PKCE challenge
+/// supported by MyAnimeList.
+async fn login(client_id: &str) -> Result<String> {
+    let verifier = Alphanumeric.sample_string(&mut rand::rng(), 128);
+    let url = format!(
+        "{OAUTH}/authorize?response_type=code&client_id={client_id}&code_challenge={verifier}&code_challenge_method=plain"
+    );
+
+    let input = Tui::get_auth_code("MyAnimeList", &url)?;
+    let code = get_code(&input);
+
+    request_token(
+        client_id,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("code_verifier", &verifier),
+        ],
+    )
+    .await
+}
+
+async fn refresh(client_id: &str) -> Result<String> {
+    let refresh_token = config::load(TABLE, "refresh_token")?;
+
+    request_token(
+        client_id,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", &refresh_token),
+        ],
+    )
+    .await
+}
+
+/// Asks the tokens and saves them, returning the access token.
+async fn request_token(client_id: &str, params: &[(&str, &str)]) -> Result<String> {
+    let mut form = vec![("client_id", client_id.to_string())];
+    // only the clients registered as web apps have a secret
+    if let Ok(secret) = config::load(TABLE, "client_secret") {
+        form.push(("client_secret", secret));
+    }
+    form.extend(params.iter().map(|&(k, v)| (k, v.to_string())));
+
+    let tokens: Tokens = Client::new()
+        .post(format!("{OAUTH}/token"))
+        .form(&form)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    config::save(TABLE, "token", &tokens.access_token)?;
+    config::save(TABLE, "refresh_token", &tokens.refresh_token)?;
+
+    Ok(tokens.access_token)
+}
+
+/// Extracts the code from a pasted redirect url, or returns the input as is.
+fn get_code(input: &str) -> String {
+    let input = input.trim();
+
+    Url::parse(input)
+        .ok()
+        .and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "code")
+                .map(|(_, value)| value.into_owned())
+        })
+        .unwrap_or_else(|| input.to_string())
+}
+
+/// MyAnimeList scores are integers from 0 to 10.
+fn to_mal_score(score: f64) -> u8 {
+    score.round().clamp(0.0, 10.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    const LIST: &str = r#"{
+        "data": [
+            {
+                "node": {"id": 52991, "title": "Sousou no Frieren", "num_episodes": 28},
+                "list_status": {"status": "watching", "num_episodes_watched": 12}
+            },
+            {
+                "node": {"id": 57334, "title": "Dandadan", "num_episodes": 0},
+                "list_status": {"status": "watching", "num_episodes_watched": 3}
+            }
+        ],
+        "paging": {"next": "https://api.myanimelist.net/v2/users/@me/animelist?offset=2"}
+    }"#;
+
+    #[test]
+    fn test_anime_list_deserialize() {
+        let list: AnimeList = serde_json::from_str(LIST).unwrap();
+
+        assert_eq!(list.data.len(), 2);
+        assert_eq!(list.data[0].node.id, 52991);
+        assert_eq!(list.data[1].list_status.num_episodes_watched, 3);
+        assert!(list.paging.next.is_some());
+    }
+
+    #[test]
+    fn test_node_progress() {
+        let list: AnimeList = serde_json::from_str(LIST).unwrap();
+        let mut items = list.data.into_iter();
+
+        let frieren = items.next().unwrap();
+        let progress = frieren.node.progress(&frieren.list_status);
+        assert_eq!(progress.latest(), EpisodeId(12));
+        assert!(progress.is_last(EpisodeId(28)));
+        assert_eq!(progress.title(), "Sousou no Frieren");
+
+        // airing series without a known total
+        let dandadan = items.next().unwrap();
+        let progress = dandadan.node.progress(&dandadan.list_status);
+        assert_eq!(progress.latest(), EpisodeId(3));
+        assert!(!progress.is_last(EpisodeId(12)));
+    }
+
+    #[test]
+    fn test_node_episodes_left() {
+        let list: AnimeList = serde_json::from_str(LIST).unwrap();
+
+        let frieren = &list.data[0];
+        assert_eq!(frieren.node.episodes_left(&frieren.list_status), 16);
+
+        // airing series without a known total
+        let dandadan = &list.data[1];
+        assert_eq!(dandadan.node.episodes_left(&dandadan.list_status), 0);
+    }
+
+    #[test_case(r#"{"status": "watching"}"#, Some(ListStatus::Current); "watching")]
+    #[test_case(r#"{"status": "completed", "is_rewatching": true}"#, Some(ListStatus::Repeating); "rewatching")]
+    #[test_case(r#"{"status": "on_hold"}"#, Some(ListStatus::Paused); "on hold")]
+    #[test_case(r#"{"status": "unknown"}"#, None; "unknown status")]
+    #[test]
+    fn test_my_list_status(json: &str, expected: Option<ListStatus>) {
+        let status: MyListStatus = serde_json::from_str(json).unwrap();
+        assert_eq!(status.status(), expected);
+    }
+
+    #[test]
+    fn test_update_form_rewatch() {
+        let form = update_form(ListStatus::Repeating, EpisodeId(0), None, Review::default());
+
+        assert!(form.contains(&("status", "completed".to_string())));
+        assert!(form.contains(&("is_rewatching", "true".to_string())));
+        assert!(form.contains(&("num_watched_episodes", "0".to_string())));
+        assert!(!form.iter().any(|(key, _)| *key == "num_times_rewatched"));
+    }
+
+    #[test]
+    fn test_update_form_complete() {
+        let review = Review {
+            score: Some(8.4),
+            notes: Some("Great ending".to_string()),
+            ..Default::default()
+        };
+        let form = update_form(ListStatus::Completed, EpisodeId(28), Some(2), review);
+
+        assert!(form.contains(&("is_rewatching", "false".to_string())));
+        assert!(form.contains(&("num_times_rewatched", "2".to_string())));
+        assert!(form.contains(&("score", "8".to_string())));
+        assert!(form.contains(&("comments", "Great ending".to_string())));
+    }
+
+    #[test_case("abc123", "abc123"; "plain code")]
+    #[test_case(" abc123 \n", "abc123"; "code with spaces")]
+    #[test_case("http://localhost/callback?code=abc123&state=x", "abc123"; "redirect url")]
+    #[test_case("http://localhost/callback?state=x", "http://localhost/callback?state=x"; "url without code")]
+    #[test]
+    fn test_get_code(input: &str, expected: &str) {
+        assert_eq!(get_code(input), expected);
+    }
+
+    #[test_case(8.0, 8; "integer score")]
+    #[test_case(7.5, 8; "rounded score")]
+    #[test_case(85.0, 10; "hundred point score clamped")]
+    #[test_case(-1.0, 0; "negative score")]
+    #[test]
+    fn test_to_mal_score(score: f64, expected: u8) {
+        assert_eq!(to_mal_score(score), expected);
+    }
+}
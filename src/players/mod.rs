@@ -0,0 +1,7 @@
+mod mpv;
+mod player;
+mod vlc;
+
+pub use mpv::Mpv;
+pub use player::{Event, Player};
+pub use vlc::Vlc;
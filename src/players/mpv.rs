@@ -0,0 +1,179 @@
+use std::path::Path;
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use futures::stream::{self, BoxStream, StreamExt};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::Command;
+use tokio_stream::wrappers::LinesStream;
+
+use super::{Event, Player};
+
+/// Tracks the playback parsing the verbose output of mpv.
+pub struct Mpv;
+
+impl Player for Mpv {
+    const NAME: &'static str = "mpv";
+
+    fn spawn(cmd: &Path, episodes: &[String], referrer: &str) -> Result<BoxStream<'static, Event>> {
+        let mut child = Command::new(cmd)
+            .arg(format!("--referrer={referrer}"))
+            .arg("-v")
+            .args(episodes)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let lines = {
+            let stdout = child
+                .stdout
+                .take()
+                .map(BufReader::new)
+                .context("Unable to get stdout")?;
+            let stderr = child
+                .stderr
+                .take()
+                .map(BufReader::new)
+                .context("Unable to get stderr")?;
+            let stdout_lines = LinesStream::new(stdout.lines());
+            let stderr_lines = LinesStream::new(stderr.lines());
+
+            stream::select(stdout_lines, stderr_lines)
+        };
+
+        // the child is kept alongside its output, so it lives as long as the stream
+        let events = stream::unfold((child, lines), |(child, mut lines)| async move {
+            loop {
+                let line = lines.next().await?.ok()?;
+                if let Some(event) = parse_line(&line) {
+                    return Some((event, (child, lines)));
+                }
+            }
+        });
+
+        Ok(events.boxed())
+    }
+}
+
+/// Parse a line of the player output into a playback event.
+fn parse_line(line: &str) -> Option<Event> {
+    match line {
+        line if line.contains("Opening done") => {
+            let url = line.split_whitespace().last()?;
+
+            Some(Event::Opened(url.to_string()))
+        }
+
+        line if line.contains('%') && !line.contains("(Paused)") => {
+            let percentage = get_percentage(line)?;
+            let remaining = get_remaining(line);
+
+            Some(Event::Position {
+                percentage,
+                remaining,
+            })
+        }
+
+        _ => None,
+    }
+}
+
+/// Extract the percentage value from a player output line.
+fn get_percentage(line: &str) -> Option<u32> {
+    let sym = line.find('%')?;
+    let bytes = line.as_bytes();
+
+    let mut start = sym;
+    while start > 0 && bytes[start - 1].is_ascii_digit() {
+        start -= 1;
+    }
+
+    if start == sym {
+        return None;
+    }
+
+    line.get(start..sym)?.parse().ok()
+}
+
+/// Extract the seconds left from the `current / total` times of a status line.
+fn get_remaining(line: &str) -> Option<u32> {
+    let tokens: Vec<_> = line.split_whitespace().collect();
+    let sep = tokens.iter().position(|t| *t == "/")?;
+
+    let current = parse_time(tokens.get(sep.checked_sub(1)?)?)?;
+    let total = parse_time(tokens.get(sep + 1)?)?;
+
+    Some(total.saturating_sub(current))
+}
+
+/// Parse a `HH:MM:SS` or `MM:SS` time into seconds.
+fn parse_time(time: &str) -> Option<u32> {
+    let mut parts = time.split(':');
+    let mut seconds = parts.next()?.parse::<u32>().ok()?;
+    for part in parts {
+        let value = part.split('.').next()?.parse::<u32>().ok()?;
+        seconds = seconds * 60 + value;
+    }
+
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    #[test_case("[status] 9%", Some(9); "single digit")]
+    #[test_case("[status] 100%", Some(100); "triple digit")]
+    #[test_case("[status] 09%", Some(9); "leading zero")]
+    #[test_case("[status] %", None; "missing digits")]
+    #[test_case("[status] no percent", None; "missing percent")]
+    #[test_case("50%", Some(50); "at start of line")]
+    #[test_case("AV: 00.00% (Paused)", Some(0); "paused parses digits before percent")]
+    #[test_case("  75%", Some(75); "with leading spaces")]
+    #[test_case("1000%", Some(1000); "four digits")]
+    #[test]
+    fn test_get_percentage(input: &str, expected: Option<u32>) {
+        assert_eq!(get_percentage(input), expected);
+    }
+
+    #[test_case("00:23:40", Some(1420); "hours minutes seconds")]
+    #[test_case("23:40", Some(1420); "minutes seconds")]
+    #[test_case("00:00:05.250", Some(5); "fractional seconds")]
+    #[test_case("--:--", None; "unknown time")]
+    #[test]
+    fn test_parse_time(time: &str, expected: Option<u32>) {
+        assert_eq!(parse_time(time), expected);
+    }
+
+    #[test_case("AV: 00:10:02 / 00:23:40 (42%) A-V:  0.000", Some(818); "status line")]
+    #[test_case("A: 00:23:40 / 00:23:40 (100%)", Some(0); "end of episode")]
+    #[test_case("[status] 42%", None; "no times")]
+    #[test]
+    fn test_get_remaining(line: &str, expected: Option<u32>) {
+        assert_eq!(get_remaining(line), expected);
+    }
+
+    #[test_case(
+        "[cplayer] Opening done: https://www.domain.tld/AnimeName_Ep_01_SUB_ITA.mp4",
+        Some(Event::Opened("https://www.domain.tld/AnimeName_Ep_01_SUB_ITA.mp4".into()));
+        "opening done"
+    )]
+    #[test_case(
+        "AV: 00:10:02 / 00:23:40 (42%)",
+        Some(Event::Position { percentage: 42, remaining: Some(818) });
+        "position"
+    )]
+    #[test_case(
+        "[status] 42%",
+        Some(Event::Position { percentage: 42, remaining: None });
+        "position without times"
+    )]
+    #[test_case("(Paused) AV: 00:10:02 / 00:23:40 (42%)", None; "paused")]
+    #[test_case("[cplayer] Set property: pause -> 1", None; "unrelated line")]
+    #[test]
+    fn test_parse_line(line: &str, expected: Option<Event>) {
+        assert_eq!(parse_line(line), expected);
+    }
+}
@@ -0,0 +1,25 @@
+use std::path::Path;
+
+use anyhow::Result;
+use futures::stream::BoxStream;
+
+/// Playback events reported by a media player.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A new playlist entry is opened, with the exact url given to the player
+    Opened(String),
+    /// Played position of the current entry, with the seconds left when known
+    Position {
+        percentage: u32,
+        remaining: Option<u32>,
+    },
+}
+
+pub trait Player {
+    /// Name of the player executable
+    const NAME: &'static str;
+
+    /// Start the player on the episodes and return its playback events.
+    /// The stream ends when the player exits.
+    fn spawn(cmd: &Path, episodes: &[String], referrer: &str) -> Result<BoxStream<'static, Event>>;
+}
@@ -0,0 +1,227 @@
+use std::collections::VecDeque;
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::Result;
+use futures::stream::{self, BoxStream, StreamExt};
+use rand::distr::{Alphanumeric, SampleString};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::process::{Child, Command};
+
+use super::{Event, Player};
+
+/// Tracks the playback polling the http interface of vlc on loopback.
+pub struct Vlc;
+
+impl Player for Vlc {
+    const NAME: &'static str = "vlc";
+
+    fn spawn(cmd: &Path, episodes: &[String], referrer: &str) -> Result<BoxStream<'static, Event>> {
+        let port = TcpListener::bind((Vlc::HOST, 0))?.local_addr()?.port();
+        let password = Alphanumeric.sample_string(&mut rand::rng(), 16);
+
+        let child = Command::new(cmd)
+            .arg(format!("--http-referrer={referrer}"))
+            .arg("--extraintf=http")
+            .arg(format!("--http-host={}", Vlc::HOST))
+            .arg(format!("--http-port={port}"))
+            .arg(format!("--http-password={password}"))
+            .args(episodes)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let state = Interface {
+            child,
+            client: Client::new(),
+            base_url: format!("http://{}:{port}/requests", Vlc::HOST),
+            password,
+            episodes: episodes.to_vec(),
+            current: None,
+            pending: VecDeque::new(),
+        };
+
+        let events = stream::unfold(state, |mut state| async move {
+            let event = state.next_event().await?;
+            Some((event, state))
+        });
+
+        Ok(events.boxed())
+    }
+}
+
+impl Vlc {
+    const HOST: &'static str = "127.0.0.1";
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Status {
+    #[serde(default)]
+    currentplid: i64,
+    /// `-1` while buffering or when the length is unknown
+    #[serde(default)]
+    length: i64,
+    #[serde(default)]
+    time: i64,
+    #[serde(default)]
+    position: f64,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct PlaylistNode {
+    #[serde(default)]
+    id: String,
+    /// `leaf` for the items, `node` for the folders
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    children: Vec<PlaylistNode>,
+}
+
+impl PlaylistNode {
+    /// Index of the item in the playlist, which follows the order of the episodes.
+    /// The playlist is found by its items, as its name is translated.
+    fn position_of(&self, plid: i64) -> Option<usize> {
+        let plid = plid.to_string();
+
+        self.find_leaf(&plid)
+    }
+
+    fn find_leaf(&self, plid: &str) -> Option<usize> {
+        self.children
+            .iter()
+            .position(|item| item.kind == "leaf" && item.id == plid)
+            .or_else(|| self.children.iter().find_map(|c| c.find_leaf(plid)))
+    }
+}
+
+/// Polling state of the vlc http interface.
+struct Interface {
+    child: Child,
+    client: Client,
+    base_url: String,
+    password: String,
+    episodes: Vec<String>,
+    current: Option<i64>,
+    pending: VecDeque<Event>,
+}
+
+impl Interface {
+    async fn next_event(&mut self) -> Option<Event> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(event);
+            }
+
+            if !matches!(self.child.try_wait(), Ok(None)) {
+                return None;
+            }
+
+            tokio::time::sleep(Vlc::POLL_INTERVAL).await;
+
+            // the interface may not be ready yet, retry on the next poll
+            let Ok(status) = self.get::<Status>("status.json").await else {
+                continue;
+            };
+
+            if status.currentplid < 0 {
+                continue;
+            }
+
+            if self.current != Some(status.currentplid) {
+                let Ok(playlist) = self.get::<PlaylistNode>("playlist.json").await else {
+                    continue;
+                };
+                let Some(url) = playlist
+                    .position_of(status.currentplid)
+                    .and_then(|i| self.episodes.get(i))
+                else {
+                    continue;
+                };
+
+                self.current = Some(status.currentplid);
+                self.pending.push_back(Event::Opened(url.clone()));
+                self.pending.push_back(Event::Position {
+                    percentage: 0,
+                    remaining: None,
+                });
+            } else if status.length > 0 {
+                let remaining = (status.length - status.time.max(0)).max(0) as u32;
+                self.pending.push_back(Event::Position {
+                    percentage: get_percentage(&status),
+                    remaining: Some(remaining),
+                });
+            }
+        }
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T> {
+        let url = format!("{}/{path}", self.base_url);
+        let response = self
+            .client
+            .get(url)
+            .basic_auth("", Some(&self.password))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json().await?)
+    }
+}
+
+fn get_percentage(status: &Status) -> u32 {
+    (status.position.clamp(0.0, 1.0) * 100.0).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use simple_test_case::test_case;
+
+    const PLAYLIST: &str = r#"{
+        "type": "node", "name": "", "id": "1",
+        "children": [
+            {
+                "type": "node", "name": "Playlist", "id": "2",
+                "children": [
+                    {"type": "leaf", "name": "Ep_01", "id": "4", "uri": "https://domain.tld/Ep_01.mp4"},
+                    {"type": "leaf", "name": "Ep_02", "id": "5", "uri": "https://domain.tld/Ep_02.mp4"}
+                ]
+            },
+            {"type": "node", "name": "Media Library", "id": "3", "children": []}
+        ]
+    }"#;
+
+    #[test]
+    fn test_position_of_translated() {
+        let playlist = PLAYLIST.replace("Playlist", "Scaletta");
+        let playlist: PlaylistNode = serde_json::from_str(&playlist).unwrap();
+
+        assert_eq!(playlist.position_of(5), Some(1));
+    }
+
+    #[test_case(4, Some(0); "first item")]
+    #[test_case(5, Some(1); "second item")]
+    #[test_case(3, None; "not in playlist")]
+    #[test_case(-1, None; "nothing playing")]
+    #[test]
+    fn test_position_of(plid: i64, expected: Option<usize>) {
+        let playlist: PlaylistNode = serde_json::from_str(PLAYLIST).unwrap();
+        assert_eq!(playlist.position_of(plid), expected);
+    }
+
+    #[test_case(r#"{"currentplid": 4, "length": 1420, "position": 0.4236}"#, 42; "playing")]
+    #[test_case(r#"{"currentplid": 4, "length": 1420, "position": 1.2}"#, 100; "clamped")]
+    #[test_case(r#"{"currentplid": 4, "length": -1, "time": -1, "position": 0.0}"#, 0; "buffering")]
+    #[test_case(r#"{"currentplid": -1}"#, 0; "stopped")]
+    #[test]
+    fn test_get_percentage(json: &str, expected: u32) {
+        let status: Status = serde_json::from_str(json).unwrap();
+        assert_eq!(get_percentage(&status), expected);
+    }
+}
@@ -15,6 +15,10 @@ use crate::{
 pub struct Search {
     pub id: Option<AnimeId>,
     pub string: String,
+    /// Other keywords for the same anime, tried when the string finds no match
+    pub alternatives: Vec<String>,
+    /// Only the result with the id is kept, nothing when it is missing
+    pub exact: bool,
 }
 
 impl Search {
@@ -22,8 +26,23 @@ impl Search {
         Self {
             id,
             string: string.into(),
+            alternatives: Vec::new(),
+            exact: false,
         }
     }
+
+    pub fn with_exact(mut self) -> Self {
+        self.exact = true;
+        self
+    }
+
+    pub fn with_alternatives(mut self, alternatives: Vec<String>) -> Self {
+        self.alternatives = alternatives
+            .into_iter()
+            .filter(|a| *a != self.string)
+            .collect();
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -107,6 +126,8 @@ pub mod selector {
     use reqwest::Client;
     use scraper::{Html, Selector};
 
+    use crate::cache::{self, Kind};
+
     pub fn from(selectors: &str) -> Selector {
         let Ok(s) = Selector::parse(selectors) else {
             panic!("unable to parse selector {selectors}")
@@ -115,9 +136,22 @@ pub mod selector {
         s
     }
 
-    pub async fn get_page(client: &Client, url: &str) -> Result<Html> {
-        let response = client.get(url).send().await?.error_for_status()?;
-        let fragment = Html::parse_fragment(&response.text().await?);
+    /// Requests a page, reading it from the cache while it is fresh.
+    pub async fn get_page(client: &Client, url: &str, kind: Kind) -> Result<Html> {
+        let text = match cache::get(kind, url) {
+            Some(text) => text,
+            None => {
+                let response = client.get(url).send().await?.error_for_status()?;
+                let text = response.text().await?;
+                cache::put(kind, url, &text);
+
+                text
+            }
+        };
+        #[cfg(test)]
+        crate::mock::record(url, &text);
+
+        let fragment = Html::parse_fragment(&text);
 
         Ok(fragment)
     }
@@ -151,7 +185,11 @@ mod tests {
     use super::*;
 
     use crate::archives::AnimeWorld;
+    use crate::cache;
+    use crate::config;
+    use crate::mock::{self, FixtureServer, MockServer};
     use reqwest::Url;
+    use serial_test::file_serial;
     use simple_test_case::test_case;
 
     pub fn get_url(raw_url: &str) -> String {
@@ -234,6 +272,14 @@ mod tests {
         assert_eq!(search.id, cloned.id);
     }
 
+    #[test]
+    fn test_search_with_alternatives() {
+        let alternatives = vec!["frieren".into(), "Sousou+no+Frieren".into()];
+        let search = Search::new("frieren", Some(AnimeId(154587))).with_alternatives(alternatives);
+
+        assert_eq!(search.alternatives, ["Sousou+no+Frieren"]);
+    }
+
     async fn scraper_single<T: Archive>(search_query: &str, expected_file: &str) -> Result<()> {
         let session_id = T::get_session_id().await.ok();
         let config = ScraperConfig {
@@ -266,10 +312,7 @@ mod tests {
 
         let search: Vec<_> = search_queries
             .into_iter()
-            .map(|s| Search {
-                string: s.into(),
-                id: None,
-            })
+            .map(|s| Search::new(s, None))
             .collect();
 
         let anime = Scraper::new(config).search::<T>(&search).await?;
@@ -327,4 +370,113 @@ mod tests {
 
     // Add each new archive here: test_archive_scraper!(AnimeWorld, MyNewArchive);
     test_archive_scraper!(AnimeWorld);
+
+    /// Runs the search against the recorded pages of the archive, with an
+    /// AniList that knows nothing of the list.
+    async fn scraper_fixtures<T: Archive>(search: Search, expected_file: &str) {
+        config::clean().ok();
+        cache::clear().unwrap();
+
+        let archive = FixtureServer::start(T::NAME);
+        let anilist = MockServer::start(vec![]);
+        config::save(T::NAME, "base_url", archive.url()).unwrap();
+        config::save("anilist", "endpoint", anilist.url()).unwrap();
+        config::save("anilist", "token", "token").unwrap();
+
+        let session_id = "sessionId=fixture;";
+        let config = ScraperConfig {
+            session_id: Some(session_id.into()),
+            proxy: None,
+            anilist_id: None,
+        };
+        let anime = Scraper::new(config).search::<T>(&[search]).await;
+
+        config::clean().unwrap();
+        cache::clear().unwrap();
+
+        let anime = anime.unwrap();
+        assert_eq!(anime.len(), 1);
+        assert_eq!(get_url(anime[0].url()), expected_file);
+
+        let requests = archive.requests();
+        assert!(!requests.is_empty());
+        for request in requests {
+            assert_eq!(request.headers.get("cookie").unwrap(), session_id);
+        }
+    }
+
+    /// Saves the pages of the archive as fixtures, run by hand when the site
+    /// changes: `cargo test record_<archive> -- --ignored`. The fixtures are
+    /// replaced only once the new pages are found right.
+    async fn record_fixtures<T: Archive>(search: Search, expected_file: &str) {
+        let fixtures = mock::fixtures_dir(T::NAME);
+        let recording = fixtures.with_file_name(format!(".{}.recording", T::NAME));
+        std::fs::remove_dir_all(&recording).ok();
+        mock::set_recording(Some(recording.clone()));
+        // the pages have to come from the site, not from the cache
+        cache::set_refresh(true);
+
+        let session_id = T::get_session_id().await.ok();
+        let config = ScraperConfig {
+            session_id,
+            proxy: None,
+            anilist_id: None,
+        };
+        let anime = Scraper::new(config).search::<T>(&[search]).await;
+
+        mock::set_recording(None);
+        cache::set_refresh(false);
+
+        let found = anime
+            .as_ref()
+            .ok()
+            .and_then(|a| a.first())
+            .map(|a| get_url(a.url()));
+        if found.as_deref() != Some(expected_file) {
+            std::fs::remove_dir_all(&recording).ok();
+        }
+        assert_eq!(found.as_deref(), Some(expected_file), "{anime:?}");
+
+        std::fs::remove_dir_all(&fixtures).ok();
+        std::fs::rename(&recording, &fixtures).unwrap();
+    }
+
+    macro_rules! test_archive_fixtures {
+        ($($archive_type:ty => ($keyword:expr, $id:expr, $file:expr)),+ $(,)?) => {
+            $(
+                paste::paste! {
+                    #[tokio::test]
+                    #[file_serial]
+                    async fn [<test_ $archive_type:lower _fixtures>]() {
+                        let search = Search::new($keyword, Some(AnimeId($id)));
+                        scraper_fixtures::<$archive_type>(search, $file).await
+                    }
+
+                    #[tokio::test]
+                    #[file_serial]
+                    #[ignore = "requires network access - refreshes the fixtures"]
+                    async fn [<record_ $archive_type:lower>]() {
+                        let search = Search::new($keyword, Some(AnimeId($id)));
+                        record_fixtures::<$archive_type>(search, $file).await
+                    }
+                }
+            )+
+        };
+    }
+
+    // Record the pages of each new archive and add it here too
+    test_archive_fixtures!(
+        AnimeWorld => (
+            "bunny+girl",
+            101291,
+            "SeishunButaYarouWaBunnyGirlSenpaiNoYumeWoMinai_Ep_01_SUB_ITA.mp4"
+        ),
+    );
+
+    #[test_case("/search?keyword=bunny+girl", "search_keyword_bunny_girl.html"; "search")]
+    #[test_case("/play/bunny-girl.Xf9oK", "play_bunny_girl_Xf9oK.html"; "series page")]
+    #[test]
+    fn test_fixture_name(path: &str, expected: &str) {
+        assert_eq!(mock::fixture_name(path), expected);
+    }
 }
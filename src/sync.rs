@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+
+use crate::config;
+
+/// When an episode is considered watched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Threshold {
+    /// Percentage of the episode that has been played (eg. `80%`)
+    Percentage(u32),
+    /// Seconds left to the end of the episode, so credits are ignored (eg. `90s`)
+    Remaining(u32),
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Self::Percentage(80)
+    }
+}
+
+impl Threshold {
+    /// Checks if the played position crosses the threshold. When the
+    /// remaining time is unknown, the default percentage is used instead.
+    pub fn is_reached(&self, percentage: u32, remaining: Option<u32>) -> bool {
+        match (self, remaining) {
+            (Self::Percentage(min), _) => percentage > *min,
+            (Self::Remaining(max), Some(remaining)) => remaining <= *max,
+            (Self::Remaining(_), None) => Self::default().is_reached(percentage, None),
+        }
+    }
+}
+
+impl FromStr for Threshold {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if let Some(value) = s.strip_suffix('%') {
+            let value = value.trim().parse()?;
+            if value > 100 {
+                bail!("percentage must be between 0 and 100");
+            }
+
+            return Ok(Self::Percentage(value));
+        }
+
+        let value = s.strip_suffix('s').context("missing `%` or `s` suffix")?;
+
+        Ok(Self::Remaining(value.trim().parse()?))
+    }
+}
+
+/// Whether progress is sent to the tracker.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Update the tracker as soon as an episode is watched
+    #[default]
+    Auto,
+    /// Ask for confirmation once the player is closed
+    Ask,
+    /// Never update the tracker (eg. guest viewers)
+    Never,
+}
+
+impl FromStr for SyncMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "ask" => Ok(Self::Ask),
+            "never" => Ok(Self::Never),
+            _ => bail!("expected one of `auto`, `ask`, `never`"),
+        }
+    }
+}
+
+/// What happens when the final episode of a series is watched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OnComplete {
+    /// Mark the series as completed
+    #[default]
+    Complete,
+    /// Ask for a score before marking the series as completed
+    Score,
+}
+
+impl FromStr for OnComplete {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "complete" => Ok(Self::Complete),
+            "score" => Ok(Self::Score),
+            _ => bail!("expected one of `complete`, `score`"),
+        }
+    }
+}
+
+/// Rules used to sync the watched episodes, from the `[sync]` config table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncPolicy {
+    pub mode: SyncMode,
+    pub threshold: Threshold,
+    pub on_complete: OnComplete,
+}
+
+impl SyncPolicy {
+    const TABLE: &str = "sync";
+
+    /// Loads the policy from the configuration, missing keys use the defaults.
+    pub fn load() -> Result<Self> {
+        let default = Self::default();
+
+        Ok(Self {
+            mode: load_or("mode", default.mode)?,
+            threshold: load_or("threshold", default.threshold)?,
+            on_complete: load_or("on_complete", default.on_complete)?,
+        })
+    }
+}
+
+fn load_or<T>(key: &str, default: T) -> Result<T>
+where
+    T: FromStr<Err = anyhow::Error>,
+{
+    let table = SyncPolicy::TABLE;
+    match config::load(table, key) {
+        Ok(value) => value
+            .parse()
+            .context(format!("Invalid configuration value `{table}.{key}`")),
+        Err(_) => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::file_serial;
+    use simple_test_case::test_case;
+
+    use super::*;
+
+    #[test_case("80%", Threshold::Percentage(80); "percentage")]
+    #[test_case(" 95 % ", Threshold::Percentage(95); "percentage with spaces")]
+    #[test_case("90s", Threshold::Remaining(90); "remaining seconds")]
+    #[test_case("0s", Threshold::Remaining(0); "end of episode")]
+    #[test]
+    fn test_threshold_from_str(s: &str, expected: Threshold) {
+        assert_eq!(s.parse::<Threshold>().unwrap(), expected);
+    }
+
+    #[test_case("80"; "missing suffix")]
+    #[test_case("101%"; "percentage above hundred")]
+    #[test_case("-5s"; "negative seconds")]
+    #[test_case("abc%"; "non numeric")]
+    #[test]
+    fn test_threshold_from_str_err(s: &str) {
+        assert!(s.parse::<Threshold>().is_err());
+    }
+
+    #[test_case(Threshold::Percentage(80), 81, None, true; "above percentage")]
+    #[test_case(Threshold::Percentage(80), 80, Some(10), false; "at percentage")]
+    #[test_case(Threshold::Remaining(90), 50, Some(90), true; "within remaining")]
+    #[test_case(Threshold::Remaining(90), 95, Some(120), false; "credits not reached")]
+    #[test_case(Threshold::Remaining(90), 85, None, true; "unknown remaining uses default")]
+    #[test]
+    fn test_threshold_is_reached(
+        threshold: Threshold,
+        percentage: u32,
+        remaining: Option<u32>,
+        expected: bool,
+    ) {
+        assert_eq!(threshold.is_reached(percentage, remaining), expected);
+    }
+
+    #[test_case("auto", SyncMode::Auto; "auto")]
+    #[test_case("Ask", SyncMode::Ask; "ask uppercase")]
+    #[test_case("never", SyncMode::Never; "never")]
+    #[test]
+    fn test_sync_mode_from_str(s: &str, expected: SyncMode) {
+        assert_eq!(s.parse::<SyncMode>().unwrap(), expected);
+    }
+
+    #[test_case("complete", OnComplete::Complete; "complete")]
+    #[test_case("score", OnComplete::Score; "score")]
+    #[test]
+    fn test_on_complete_from_str(s: &str, expected: OnComplete) {
+        assert_eq!(s.parse::<OnComplete>().unwrap(), expected);
+    }
+
+    #[test]
+    #[file_serial]
+    fn test_sync_policy_load() {
+        config::clean().ok();
+        assert_eq!(SyncPolicy::load().unwrap(), SyncPolicy::default());
+
+        config::save("sync", "mode", "never").unwrap();
+        config::save("sync", "threshold", "90s").unwrap();
+        let policy = SyncPolicy::load().unwrap();
+        assert_eq!(policy.mode, SyncMode::Never);
+        assert_eq!(policy.threshold, Threshold::Remaining(90));
+        assert_eq!(policy.on_complete, OnComplete::Complete);
+
+        config::save("sync", "on_complete", "rate").unwrap();
+        assert!(SyncPolicy::load().is_err());
+        config::clean().unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::str::FromStr;
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    anilist::{Anilist, AnilistId, Progress, Review, ScoreFormat, WatchingAnime},
+    anime::{AnimeId, EpisodeId},
+    config,
+    kitsu::Kitsu,
+    myanimelist::MyAnimeList,
+};
+
+/// A service keeping the list of the watched anime. Anime are always
+/// identified by their AniList id, as found on the archives.
+pub trait Tracker {
+    fn get_watching_list(&self) -> impl Future<Output = Option<Vec<WatchingAnime>>> + Send;
+
+    fn get_progress(&self, id: AnimeId) -> impl Future<Output = Option<Progress>> + Send;
+
+    /// Progress of many anime at once, missing the ones not in the list.
+    fn get_progress_batch(
+        &self,
+        ids: &[AnimeId],
+    ) -> impl Future<Output = HashMap<AnimeId, Progress>> + Send;
+
+    /// Sets the watched episode, marking the anime as completed after the last one.
+    fn update(&mut self, id: AnimeId, number: EpisodeId)
+    -> impl Future<Output = Result<()>> + Send;
+
+    /// Marks the anime as completed at the given episode, with an optional review.
+    fn complete(
+        &mut self,
+        id: AnimeId,
+        number: EpisodeId,
+        review: Review,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Starts a rewatch of the anime, its progress starts again from zero.
+    fn rewatch(&mut self, id: AnimeId) -> impl Future<Output = Result<()>> + Send;
+
+    /// The scale the user gives scores in.
+    fn score_format(&self) -> impl Future<Output = ScoreFormat> + Send;
+}
+
+/// Trackers that can be chosen in the `[tracker]` config table.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TrackerKind {
+    #[default]
+    Anilist,
+    MyAnimeList,
+    Kitsu,
+}
+
+impl FromStr for TrackerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "anilist" => Ok(Self::Anilist),
+            "myanimelist" | "mal" => Ok(Self::MyAnimeList),
+            "kitsu" => Ok(Self::Kitsu),
+            _ => bail!("expected one of `anilist`, `myanimelist`, `kitsu`"),
+        }
+    }
+}
+
+impl TrackerKind {
+    /// Loads the tracker from the configuration, AniList when unset.
+    pub fn load() -> Result<Self> {
+        match config::load("tracker", "name") {
+            Ok(value) => value
+                .parse()
+                .context("Invalid configuration value `tracker.name`"),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+/// The tracker chosen in the configuration.
+#[derive(Debug)]
+pub enum AnyTracker {
+    Anilist(Anilist),
+    MyAnimeList(MyAnimeList),
+    Kitsu(Kitsu),
+}
+
+impl Default for AnyTracker {
+    fn default() -> Self {
+        Self::Anilist(Anilist::default())
+    }
+}
+
+impl AnyTracker {
+    pub async fn new(anilist_id: Option<AnilistId>) -> Result<Self> {
+        let tracker = match TrackerKind::load()? {
+            TrackerKind::Anilist => Self::Anilist(Anilist::new(anilist_id)?),
+            TrackerKind::MyAnimeList => Self::MyAnimeList(MyAnimeList::new().await?),
+            TrackerKind::Kitsu => Self::Kitsu(Kitsu::new().await?),
+        };
+
+        Ok(tracker)
+    }
+}
+
+impl Tracker for AnyTracker {
+    async fn get_watching_list(&self) -> Option<Vec<WatchingAnime>> {
+        match self {
+            Self::Anilist(t) => t.get_watching_list().await,
+            Self::MyAnimeList(t) => t.get_watching_list().await,
+            Self::Kitsu(t) => t.get_watching_list().await,
+        }
+    }
+
+    async fn get_progress(&self, id: AnimeId) -> Option<Progress> {
+        match self {
+            Self::Anilist(t) => t.get_progress(id).await,
+            Self::MyAnimeList(t) => t.get_progress(id).await,
+            Self::Kitsu(t) => t.get_progress(id).await,
+        }
+    }
+
+    async fn get_progress_batch(&self, ids: &[AnimeId]) -> HashMap<AnimeId, Progress> {
+        match self {
+            Self::Anilist(t) => t.get_progress_batch(ids).await,
+            Self::MyAnimeList(t) => t.get_progress_batch(ids).await,
+            Self::Kitsu(t) => t.get_progress_batch(ids).await,
+        }
+    }
+
+    async fn update(&mut self, id: AnimeId, number: EpisodeId) -> Result<()> {
+        match self {
+            Self::Anilist(t) => t.update(id, number).await,
+            Self::MyAnimeList(t) => t.update(id, number).await,
+            Self::Kitsu(t) => t.update(id, number).await,
+        }
+    }
+
+    async fn complete(&mut self, id: AnimeId, number: EpisodeId, review: Review) -> Result<()> {
+        match self {
+            Self::Anilist(t) => t.complete(id, number, review).await,
+            Self::MyAnimeList(t) => t.complete(id, number, review).await,
+            Self::Kitsu(t) => t.complete(id, number, review).await,
+        }
+    }
+
+    async fn rewatch(&mut self, id: AnimeId) -> Result<()> {
+        match self {
+            Self::Anilist(t) => t.rewatch(id).await,
+            Self::MyAnimeList(t) => t.rewatch(id).await,
+            Self::Kitsu(t) => t.rewatch(id).await,
+        }
+    }
+
+    async fn score_format(&self) -> ScoreFormat {
+        match self {
+            Self::Anilist(t) => t.score_format().await,
+            Self::MyAnimeList(t) => t.score_format().await,
+            Self::Kitsu(t) => t.score_format().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::file_serial;
+    use simple_test_case::test_case;
+
+    use super::*;
+
+    #[test_case("anilist", TrackerKind::Anilist; "anilist")]
+    #[test_case("MyAnimeList", TrackerKind::MyAnimeList; "myanimelist mixed case")]
+    #[test_case(" mal ", TrackerKind::MyAnimeList; "mal shorthand")]
+    #[test_case("kitsu", TrackerKind::Kitsu; "kitsu")]
+    #[test]
+    fn test_tracker_kind_from_str(s: &str, expected: TrackerKind) {
+        assert_eq!(s.parse::<TrackerKind>().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_tracker_kind_from_str_err() {
+        assert!("shikimori".parse::<TrackerKind>().is_err());
+    }
+
+    #[test]
+    #[file_serial]
+    fn test_tracker_kind_load() {
+        config::clean().ok();
+        assert_eq!(TrackerKind::load().unwrap(), TrackerKind::Anilist);
+
+        config::save("tracker", "name", "myanimelist").unwrap();
+        assert_eq!(TrackerKind::load().unwrap(), TrackerKind::MyAnimeList);
+
+        config::save("tracker", "name", "unknown").unwrap();
+        assert!(TrackerKind::load().is_err());
+        config::clean().unwrap();
+    }
+}
@@ -1,4 +1,5 @@
 use anyhow::{Result, bail};
+use chrono::NaiveDate;
 use owo_colors::OwoColorize;
 use rustyline::{ColorMode, DefaultEditor, config::Configurer, error::ReadlineError};
 
@@ -28,6 +29,70 @@ pub fn get_command() -> Result<Command> {
     Ok(cmd)
 }
 
+/// Reads a password without showing it, nor keeping it in the history
+#[cfg(not(test))]
+pub fn get_password() -> Result<Option<String>> {
+    use std::borrow::Cow;
+
+    use rustyline::{
+        Editor, Helper,
+        completion::Completer,
+        highlight::{CmdKind, Highlighter},
+        hint::Hinter,
+        validate::Validator,
+    };
+
+    /// Hides the typed characters
+    struct Masking;
+
+    impl Highlighter for Masking {
+        fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+            Cow::Owned("*".repeat(line.chars().count()))
+        }
+
+        fn highlight_char(&self, _line: &str, _pos: usize, kind: CmdKind) -> bool {
+            kind != CmdKind::MoveCursor
+        }
+    }
+
+    impl Completer for Masking {
+        type Candidate = String;
+    }
+
+    impl Hinter for Masking {
+        type Hint = String;
+    }
+
+    impl Validator for Masking {}
+
+    impl Helper for Masking {}
+
+    let mut rl = Editor::new()?;
+    rl.set_helper(Some(Masking));
+    rl.set_color_mode(ColorMode::Forced);
+    rl.set_auto_add_history(false);
+    let prompt = "~❯ ".red().to_string();
+    let password = match rl.readline(&prompt) {
+        Ok(line) => Some(line),
+        Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => None,
+        Err(_) => bail!(TuiError::InvalidInput),
+    };
+
+    Ok(password)
+}
+
+/// Parses a date as `YYYY-MM-DD`, an empty line is the given day and `-` no date
+pub fn parse_date(line: &str, today: NaiveDate) -> Result<Option<NaiveDate>> {
+    match line.trim() {
+        "" => Ok(Some(today)),
+        "-" => Ok(None),
+        line => match NaiveDate::parse_from_str(line, "%Y-%m-%d") {
+            Ok(date) => Ok(Some(date)),
+            Err(_) => bail!(TuiError::InvalidInput),
+        },
+    }
+}
+
 /// Parses a selection string into a list of indices
 ///
 /// Supports formats like:
@@ -78,6 +143,23 @@ mod tests {
     use super::*;
     use simple_test_case::test_case;
 
+    #[test_case("", NaiveDate::from_ymd_opt(2025, 10, 20); "empty is today")]
+    #[test_case(" - ", None; "dash skips")]
+    #[test_case("2025-09-28", NaiveDate::from_ymd_opt(2025, 9, 28); "full date")]
+    #[test]
+    fn test_parse_date(line: &str, expected: Option<NaiveDate>) {
+        let today = NaiveDate::from_ymd_opt(2025, 10, 20).unwrap();
+        assert_eq!(parse_date(line, today).unwrap(), expected);
+    }
+
+    #[test_case("28/09/2025"; "wrong format")]
+    #[test_case("2025-02-30"; "invalid day")]
+    #[test]
+    fn test_parse_date_err(line: &str) {
+        let today = NaiveDate::from_ymd_opt(2025, 10, 20).unwrap();
+        assert!(parse_date(line, today).is_err());
+    }
+
     #[test_case("1,2,3", vec![1, 2, 3]; "comma separated")]
     #[test_case("1-5", vec![1, 2, 3, 4, 5]; "closed range")]
     #[test_case("1-3, 6", vec![1, 2, 3, 6]; "range and single")]
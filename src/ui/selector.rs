@@ -1,11 +1,11 @@
 use std::process::exit;
 
-use anyhow::{Result, ensure};
+use anyhow::{Result, bail, ensure};
 
 use super::input::{Command, get_command, get_selection};
 use super::table::{build_episodes_table, build_table, print_prompt, print_title};
 use crate::{
-    anilist::WatchingAnime,
+    anilist::{AiredEpisode, Airing, ListEntry, MediaInfo, RelatedMedia, WatchingAnime},
     anime::{Anime, EpisodeId},
     error::TuiError,
     range::Range,
@@ -13,6 +13,8 @@ use crate::{
 
 /// Selects from a list of watching anime
 pub fn select_from_watching(series: &[WatchingAnime]) -> Result<Vec<&WatchingAnime>> {
+    // the lists are known only on AniList
+    let with_lists = series.iter().any(|s| !s.lists().is_empty());
     let rows = {
         let mut rows = Vec::new();
         for (i, c) in series.iter().enumerate() {
@@ -20,12 +22,20 @@ pub fn select_from_watching(series: &[WatchingAnime]) -> Result<Vec<&WatchingAni
                 0 => "•".to_string(),
                 n => n.to_string(),
             };
-            rows.push(vec![(i + 1).to_string(), c.title().to_string(), watched]);
+            let mut row = vec![(i + 1).to_string(), c.title().to_string(), watched];
+            if with_lists {
+                row.push(c.lists().join(", "));
+            }
+            rows.push(row);
         }
         rows
     };
 
-    let table = build_table(vec!["Index", "Name", "To See"], rows);
+    let mut header = vec!["Index", "Name", "To See"];
+    if with_lists {
+        header.push("List");
+    }
+    let table = build_table(header, rows);
 
     print_title("You are watching these series");
     println!("{table}");
@@ -72,6 +82,146 @@ pub fn select_series(series: &mut Vec<Anime>) -> Result<()> {
     Ok(())
 }
 
+/// Prints the entries of the user list
+pub fn show_list(entries: &[ListEntry]) {
+    let rows = entries
+        .iter()
+        .map(|e| {
+            let score = e.score().map(|s| s.to_string()).unwrap_or("•".to_string());
+            vec![
+                e.id().to_string(),
+                e.title().to_string(),
+                e.status().to_string(),
+                e.progress(),
+                score,
+            ]
+        })
+        .collect();
+
+    let table = build_table(vec!["Id", "Name", "Status", "Progress", "Score"], rows);
+
+    println!("{table}");
+}
+
+/// Prints the episodes airing in a day
+pub fn show_schedule(day: &str, episodes: &[(String, &Airing)]) {
+    let rows = episodes
+        .iter()
+        .map(|(time, a)| vec![time.clone(), a.title().to_string(), a.episode().to_string()])
+        .collect();
+
+    let table = build_table(vec!["Time", "Name", "Episode"], rows);
+
+    print_title(day);
+    println!("{table}\n");
+}
+
+/// Prints the aired episodes with their local notification time and the
+/// link to the archive, when found
+pub fn show_notifications(aired: &[(String, &AiredEpisode, Option<String>)]) {
+    let rows = aired
+        .iter()
+        .map(|(time, a, link)| {
+            vec![
+                time.clone(),
+                a.media().title().to_string(),
+                a.episode().to_string(),
+                link.clone().unwrap_or_else(|| "not found".to_string()),
+            ]
+        })
+        .collect();
+
+    let table = build_table(vec!["Aired", "Name", "Episode", "Link"], rows);
+
+    println!("{table}\n");
+}
+
+/// Selects a single anime from the AniList search results, none when skipped
+pub fn select_media(results: &[MediaInfo]) -> Result<Option<&MediaInfo>> {
+    let rows = results
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let unknown = || "?".to_string();
+            vec![
+                (i + 1).to_string(),
+                m.title().to_string(),
+                m.synonyms().first().cloned().unwrap_or_default(),
+                m.format().map(str::to_string).unwrap_or_else(unknown),
+                m.year().map(|y| y.to_string()).unwrap_or_else(unknown),
+                m.episodes().map(|e| e.to_string()).unwrap_or_else(unknown),
+            ]
+        })
+        .collect();
+
+    let headers = vec![
+        "Index",
+        "Name",
+        "Also Known As",
+        "Format",
+        "Year",
+        "Episodes",
+    ];
+    let table = build_table(headers, rows);
+
+    println!("{table}");
+    print_prompt("Select the anime (eg: 1) [<enter> to skip, <q> for exit]");
+
+    select_one(results)
+}
+
+/// Selects a single anime among the related ones, none when skipped
+pub fn select_relation<'a>(
+    title: &str,
+    related: &'a [RelatedMedia],
+) -> Result<Option<&'a RelatedMedia>> {
+    let rows = related
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let unknown = || "?".to_string();
+            let m = r.media();
+            vec![
+                (i + 1).to_string(),
+                r.relation().to_string(),
+                m.title().to_string(),
+                m.format().map(str::to_string).unwrap_or_else(unknown),
+                m.year().map(|y| y.to_string()).unwrap_or_else(unknown),
+                m.episodes().map(|e| e.to_string()).unwrap_or_else(unknown),
+            ]
+        })
+        .collect();
+
+    let headers = vec!["Index", "Relation", "Name", "Format", "Year", "Episodes"];
+    let table = build_table(headers, rows);
+
+    print_title(&format!("Related to {title}"));
+    println!("{table}");
+    print_prompt("Select the anime to search (eg: 1) [<enter> to skip, <q> for exit]");
+
+    select_one(related)
+}
+
+/// Reads the index of a single item, none when the input is empty
+fn select_one<T>(items: &[T]) -> Result<Option<&T>> {
+    let item = match get_command()? {
+        Command::Default(input) if input.trim().is_empty() => None,
+        Command::Default(input) => match get_selection(&input, 1, items.len())?.as_slice() {
+            [index] => Some(
+                items
+                    .get(usize::from(*index) - 1)
+                    .ok_or(TuiError::InvalidInput)?,
+            ),
+            _ => bail!(TuiError::InvalidInput),
+        },
+        Command::Unwatched => bail!(TuiError::InvalidInput),
+        Command::Quit => exit(0),
+    };
+    println!();
+
+    Ok(item)
+}
+
 /// Selects episodes from an anime
 pub fn select_episodes(anime: &Anime) -> Result<Vec<String>> {
     let last_watched = anime.last_watched();
@@ -2,7 +2,13 @@ use anyhow::Result;
 use indicatif::ProgressBar;
 
 use super::{progress::ProgressManager, selector};
-use crate::{anilist::WatchingAnime, anime::Anime};
+use crate::{
+    anilist::{
+        AiredEpisode, Airing, ListEntry, MediaInfo, RelatedMedia, Review, ScoreFormat,
+        WatchingAnime,
+    },
+    anime::Anime,
+};
 
 /// Main TUI struct for managing terminal user interface
 #[derive(Default)]
@@ -33,6 +39,29 @@ impl Tui {
         selector::select_episodes(anime)
     }
 
+    pub fn show_list(entries: &[ListEntry]) {
+        selector::show_list(entries)
+    }
+
+    pub fn show_schedule(day: &str, episodes: &[(String, &Airing)]) {
+        selector::show_schedule(day, episodes)
+    }
+
+    pub fn show_notifications(aired: &[(String, &AiredEpisode, Option<String>)]) {
+        selector::show_notifications(aired)
+    }
+
+    pub fn select_media(results: &[MediaInfo]) -> Result<Option<&MediaInfo>> {
+        selector::select_media(results)
+    }
+
+    pub fn select_relation<'a>(
+        title: &str,
+        related: &'a [RelatedMedia],
+    ) -> Result<Option<&'a RelatedMedia>> {
+        selector::select_relation(title, related)
+    }
+
     pub fn get_token(url: &str) -> Result<String> {
         #[cfg(test)]
         {
@@ -61,6 +90,143 @@ impl Tui {
         }
     }
 
+    /// Asks the authorization code of an OAuth login, the url it redirects to is
+    /// accepted too.
+    pub fn get_auth_code(service: &str, url: &str) -> Result<String> {
+        #[cfg(test)]
+        {
+            let _ = (service, url);
+            Ok(String::new())
+        }
+
+        #[cfg(not(test))]
+        {
+            use std::process::exit;
+
+            use super::input;
+
+            use owo_colors::OwoColorize;
+
+            let oauth = format!("{service} Oauth").cyan().bold().to_string();
+            let action = "Authenticate to:".green().to_string();
+            let url = url.magenta().bold().to_string();
+            let input = ":: ".red().to_string()
+                + &"Paste the code or the redirect url here:"
+                    .bold()
+                    .to_string();
+            println!("{oauth}\n\n{action} {url}\n\n{input}");
+
+            match input::get_command()? {
+                input::Command::Default(line) => Ok(line),
+                _ => exit(0),
+            }
+        }
+    }
+
+    /// Asks the username and the password of a service.
+    pub fn get_credentials(service: &str) -> Result<(String, String)> {
+        #[cfg(test)]
+        {
+            let _ = service;
+            Ok((String::new(), String::new()))
+        }
+
+        #[cfg(not(test))]
+        {
+            use std::process::exit;
+
+            use super::{input, table::print_prompt};
+
+            use owo_colors::OwoColorize;
+
+            println!("{}", format!("{service} login").cyan().bold());
+
+            print_prompt("Username:");
+            let username = match input::get_command()? {
+                input::Command::Default(line) => line,
+                _ => exit(0),
+            };
+
+            print_prompt("Password:");
+            let Some(password) = input::get_password()? else {
+                exit(0)
+            };
+            println!();
+
+            Ok((username, password))
+        }
+    }
+
+    pub fn confirm(question: &str) -> Result<bool> {
+        #[cfg(test)]
+        {
+            let _ = question;
+            Ok(true)
+        }
+
+        #[cfg(not(test))]
+        {
+            use super::{input, table::print_prompt};
+
+            print_prompt(&format!("{question} [Y/n]"));
+
+            let answer = match input::get_command()? {
+                input::Command::Default(line) => line.is_empty() || line.eq_ignore_ascii_case("y"),
+                _ => false,
+            };
+            println!();
+
+            Ok(answer)
+        }
+    }
+
+    /// Asks the score, the notes and the finish date of a completed series.
+    pub fn get_review(title: &str, format: ScoreFormat) -> Result<Review> {
+        #[cfg(test)]
+        {
+            let _ = (title, format);
+            Ok(Review::default())
+        }
+
+        #[cfg(not(test))]
+        {
+            use anyhow::bail;
+
+            use super::{input, table::print_prompt};
+            use crate::error::TuiError;
+
+            print_prompt(&format!("Score for {title} ({format}) [<enter> to skip]"));
+            let score = match input::get_command()? {
+                input::Command::Default(line) if line.is_empty() => None,
+                input::Command::Default(line) => match format.parse(&line) {
+                    Some(score) => Some(score),
+                    None => bail!(TuiError::InvalidInput),
+                },
+                _ => None,
+            };
+
+            print_prompt("Notes [<enter> to skip]");
+            let notes = match input::get_command()? {
+                input::Command::Default(line) if !line.is_empty() => Some(line),
+                _ => None,
+            };
+
+            print_prompt("Finish date YYYY-MM-DD [<enter> for today, - to skip]");
+            let today = chrono::Local::now().date_naive();
+            let completed_at = match input::get_command()? {
+                input::Command::Default(line) => input::parse_date(&line, today)?,
+                _ => None,
+            };
+            println!();
+
+            Ok(Review {
+                score,
+                notes,
+                completed_at,
+            })
+        }
+    }
+
     pub fn get_session_id(archive: &str) -> Result<String> {
         #[cfg(test)]
         {
@@ -133,6 +299,30 @@ mod tests {
         assert_eq!(result.unwrap(), "");
     }
 
+    #[test]
+    fn test_get_auth_code_returns_empty_in_test() {
+        let result =
+            Tui::get_auth_code("MyAnimeList", "https://myanimelist.net/v1/oauth2/authorize");
+        assert_eq!(result.unwrap(), "");
+    }
+
+    #[test]
+    fn test_get_credentials_returns_empty_in_test() {
+        let (username, password) = Tui::get_credentials("Kitsu").unwrap();
+        assert!(username.is_empty() && password.is_empty());
+    }
+
+    #[test]
+    fn test_confirm_returns_true_in_test() {
+        assert!(Tui::confirm("Update?").unwrap());
+    }
+
+    #[test]
+    fn test_get_review_returns_default_in_test() {
+        let review = Tui::get_review("Title", ScoreFormat::Point100).unwrap();
+        assert_eq!(review, Review::default());
+    }
+
     #[test_case("AnimeWorld"; "animeworld archive")]
     #[test_case("SomeOtherArchive"; "other archive")]
     #[test]